@@ -0,0 +1 @@
+var LINGDOCS_SEARCH_INDEX = {"page":"dict.html","ignore":["-","'"],"entries":[["kata","kata","kata","noun","cat; small animal"],["katido","katido","katido","noun","kitten"],["kita","kita","kita","adjective","small"],["mila","mila","mila","verb","to love"],["nomi","nomi","nomi","verb","to name"],["ngulo","ngulo","ngulo","noun","corner"],["pita","pita","pita","noun","bread"],["ŝalo","salo","ŝalo","noun","scarf"]]};
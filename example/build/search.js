@@ -0,0 +1,140 @@
+// Dictionary search, using index from `search-index.js`
+// Index is loaded as a script, not fetched, so search works from `file://`
+(function () {
+  var index = window.LINGDOCS_SEARCH_INDEX;
+  var input = document.querySelector(".dict-search-input");
+  var results = document.querySelector(".dict-search-results");
+  if (!index || !input || !results) {
+    return;
+  }
+
+  var MAX_RESULTS = 30;
+
+  // Lowercase, without diacritics or ignored characters, same as index
+  function normalize(text) {
+    text = text.toLowerCase();
+    index.ignore.forEach(function (ignored) {
+      text = text.split(ignored).join("");
+    });
+    return text.normalize("NFD").replace(/\p{M}/gu, "");
+  }
+
+  var entries = index.entries.map(function (entry, order) {
+    return {
+      headword: entry[0],
+      normalized: entry[1],
+      id: entry[2],
+      pos: entry[3],
+      gloss: entry[4],
+      glossWords: normalize(entry[4])
+        .split(/[^\p{L}\p{N}]+/u)
+        .filter(Boolean),
+      // Entries are in collation order
+      order: order,
+    };
+  });
+
+  // Edit distance, or `max + 1` if greater than `max`
+  function distance(a, b, max) {
+    if (Math.abs(a.length - b.length) > max) {
+      return max + 1;
+    }
+    var prev = [];
+    for (var j = 0; j <= b.length; j++) {
+      prev.push(j);
+    }
+    for (var i = 1; i <= a.length; i++) {
+      var curr = [i];
+      var rowMin = i;
+      for (var j = 1; j <= b.length; j++) {
+        var cost = a[i - 1] === b[j - 1] ? 0 : 1;
+        curr.push(Math.min(prev[j] + 1, curr[j - 1] + 1, prev[j - 1] + cost));
+        rowMin = Math.min(rowMin, curr[j]);
+      }
+      if (rowMin > max) {
+        return max + 1;
+      }
+      prev = curr;
+    }
+    return prev[b.length];
+  }
+
+  // Rank of match, lower is better, or `null` if no match
+  function rank(entry, query, maxDistance) {
+    if (entry.normalized === query) {
+      return 0;
+    }
+    if (entry.normalized.indexOf(query) === 0) {
+      return 1;
+    }
+    if (entry.glossWords.indexOf(query) >= 0) {
+      return 2;
+    }
+    if (entry.glossWords.some(function (word) { return word.indexOf(query) === 0; })) {
+      return 3;
+    }
+    if (maxDistance > 0) {
+      if (distance(entry.normalized, query, maxDistance) <= maxDistance) {
+        return 4;
+      }
+      if (entry.glossWords.some(function (word) {
+        return distance(word, query, maxDistance) <= maxDistance;
+      })) {
+        return 5;
+      }
+    }
+    return null;
+  }
+
+  function search(text) {
+    var query = normalize(text.trim());
+    if (!query) {
+      return [];
+    }
+    var maxDistance = query.length < 4 ? 0 : query.length < 7 ? 1 : 2;
+
+    var matches = [];
+    entries.forEach(function (entry) {
+      var entryRank = rank(entry, query, maxDistance);
+      if (entryRank !== null) {
+        matches.push({ entry: entry, rank: entryRank });
+      }
+    });
+    matches.sort(function (a, b) {
+      return a.rank - b.rank || a.entry.order - b.entry.order;
+    });
+    return matches.slice(0, MAX_RESULTS).map(function (match) {
+      return match.entry;
+    });
+  }
+
+  function element(tag, className, text) {
+    var node = document.createElement(tag);
+    node.className = className;
+    node.textContent = text;
+    return node;
+  }
+
+  function render() {
+    results.innerHTML = "";
+    search(input.value).forEach(function (entry) {
+      var item = document.createElement("li");
+      item.className = "dict-search-result";
+
+      var link = element("a", "link", entry.headword);
+      link.href = index.page + "#" + entry.id;
+      item.appendChild(link);
+      if (entry.pos) {
+        item.appendChild(document.createTextNode(" "));
+        item.appendChild(element("span", "pos", entry.pos));
+      }
+      item.appendChild(document.createTextNode(" "));
+      item.appendChild(element("span", "gloss", entry.gloss));
+
+      results.appendChild(item);
+    });
+  }
+
+  input.addEventListener("input", render);
+  render();
+})();
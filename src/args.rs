@@ -1,6 +1,106 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use lingdocs::DictFormat;
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Args {
-  pub dir: Option<String>,  
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Project directory, to build without a command
+    pub dir: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Build project
+    Build {
+        /// Project directory
+        dir: Option<String>,
+        /// Build profile from `Lingdocs.toml`
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Show statistics of dictionary
+    Stats {
+        /// Project directory
+        dir: Option<String>,
+        /// Write as JSON
+        #[arg(long)]
+        json: bool,
+        /// Previous export of dictionary, to show growth since
+        #[arg(long)]
+        previous: Option<String>,
+    },
+    /// Show concepts of concept lists, such as Swadesh list, which have no word in dictionary
+    Coverage {
+        /// Project directory
+        dir: Option<String>,
+        /// Built-in list (`swadesh-100`, `swadesh-207`, `leipzig-jakarta`) or `.llst` file in
+        /// source directory, default is `dictionary.concept_lists`
+        #[arg(long)]
+        list: Vec<String>,
+    },
+    /// Apply sound changes to words of word list, and write word list with form after each stage
+    Sca {
+        /// Word list (`.llst` file)
+        file: String,
+        /// Project directory
+        dir: Option<String>,
+        /// File of sound changes in `files.sound_changes`, without extension, needed if project
+        /// has more than one
+        #[arg(long)]
+        rules: Option<String>,
+        /// Flag for `@if` rules, as well as `options.flags`
+        #[arg(long)]
+        flag: Vec<String>,
+        /// Show each rule which changes each word, instead of word list
+        #[arg(long)]
+        trace: bool,
+        /// Output file, instead of stdout
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Convert dictionary to and from other formats, or write derivation graph
+    Dict {
+        #[command(subcommand)]
+        command: DictCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DictCommand {
+    /// Write dictionary as table or LIFT
+    Export {
+        /// Project directory
+        dir: Option<String>,
+        #[arg(long, value_enum)]
+        format: DictFormat,
+        /// Output file, instead of stdout
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Replace dictionary with table or LIFT, and show changed entries
+    Import {
+        /// Table or LIFT file
+        file: String,
+        /// Project directory
+        dir: Option<String>,
+        /// Format of file, default is from file extension
+        #[arg(long, value_enum)]
+        format: Option<DictFormat>,
+        /// Show changed entries, without writing dictionary
+        #[arg(long)]
+        dry_run: bool,
+        /// Replace dictionary even if its comments are removed
+        #[arg(long)]
+        force: bool,
+    },
+    /// Write derivation graph of dictionary in Graphviz DOT format
+    Graph {
+        /// Project directory
+        dir: Option<String>,
+        /// Output file, instead of stdout
+        #[arg(long, short)]
+        output: Option<String>,
+    },
 }
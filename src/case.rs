@@ -3,7 +3,7 @@ use regex::Regex;
 /// Uppercase first letter of each word, lowercase rest
 pub fn upper_first(s: &str) -> String {
     s.split_whitespace()
-        .map(|x| upper_first_once(x))
+        .map(upper_first_once)
         .collect::<Vec<String>>()
         .join(" ")
 }
@@ -1,8 +1,24 @@
-use std::{error::Error, fs, path::Path};
+use std::{collections::HashMap, error::Error, fs, path::Path};
 
-use regex::Regex;
+use walkdir::WalkDir;
 
-use crate::{case, convert, utils::separate_filename_ext, Config, MyError};
+use crate::{
+    case,
+    convert::{self, Document},
+    diagnostic::Diagnostics,
+    dict::{self, Dictionary, Lookup},
+    front_matter::{split_front_matter, FrontMatter},
+    orthography::Orthography,
+    phonotactics::Phonotactics,
+    preprocess::Preprocessor,
+    rewrite::Rewrite,
+    sound_change::SoundChanges,
+    template::{Template, Value},
+    utils::{self, relative_root, separate_filename_ext},
+    variables::project_variables,
+    word_list::WordList,
+    Config, MyError,
+};
 
 pub fn compile(config: Config) -> Result<(), Box<dyn Error>> {
     // Remove build directory recursively if exists
@@ -12,13 +28,7 @@ pub fn compile(config: Config) -> Result<(), Box<dyn Error>> {
     // Create new build directory
     fs::create_dir(&config.files.build).expect("Could not create build directory");
 
-    // Template file
-    let path = format!("{}/{}", config.files.source, config.files.template);
-    let template_html = if Path::new(&path).exists() {
-        Some(fs::read_to_string(&path).expect("Could not read template file"))
-    } else {
-        None
-    };
+    let mut layouts = Layouts::new(&config)?;
 
     // Convert scss to css
     if let Some(filepath) = &config.files.style {
@@ -42,70 +52,640 @@ pub fn compile(config: Config) -> Result<(), Box<dyn Error>> {
         .expect("Could not write css file");
     }
 
+    // Source files, with path relative to source directory
+    let layouts_dir = Path::new(&config.files.source).join(&config.files.layouts);
+    let sound_changes_dir = Path::new(&config.files.source).join(&config.files.sound_changes);
     let mut files: Vec<(String, String)> = Vec::new();
-    for entry in fs::read_dir(&config.files.source)
-        .expect("Could not read source directory")
-        .flatten()
+    for entry in WalkDir::new(&config.files.source)
+        .min_depth(1)
+        .sort_by_file_name()
     {
-        // Throw if not file
-        if !entry.path().is_file() {
-            return Err(Box::new(MyError(
-                "Should not be folder in source directory".to_string(),
-            )));
+        let entry = entry?;
+        // Skip folders, and layouts and sound changes which are read separately
+        if !entry.file_type().is_file()
+            || entry.path().starts_with(&layouts_dir)
+            || entry.path().starts_with(&sound_changes_dir)
+        {
+            continue;
         }
 
-        // Get file name
-        //TODO Fix this
-        let filename = match entry.path().file_name() {
-            Some(x) => x.to_string_lossy().to_string(),
-            None => continue,
-        };
+        let filepath = entry
+            .path()
+            .strip_prefix(&config.files.source)?
+            .to_string_lossy()
+            .replace('\\', "/");
 
         // Add file to list
         files.push((
-            filename,
+            filepath,
             fs::read_to_string(entry.path()).expect("Could not read source file"),
         ));
     }
 
+    // Converted pages, rendered with template after all are converted
+    let mut pages = Vec::<Page>::new();
+    let mut diagnostics = Diagnostics::default();
+    let orthography = Orthography::new(&config.orthography);
+    let phonotactics = Phonotactics::load(&config, &mut diagnostics);
+    // Letters of alphabet are not split in transcription rules, and IPA has no multigraphs
+    let letters = orthography.variants().cloned().collect::<Vec<_>>();
+    let transcription = Rewrite::load(
+        &config,
+        &config.files.transcription,
+        &letters,
+        &mut diagnostics,
+    );
+    let allophony = Rewrite::load(&config, &config.files.allophony, &[], &mut diagnostics);
+    let sound_changes = SoundChanges::load_all(&config, &mut diagnostics);
+    let dictionary = load_dictionary(
+        &config,
+        &orthography,
+        phonotactics.as_ref(),
+        transcription.as_ref(),
+        &mut diagnostics,
+    );
+    let preprocessor = Preprocessor::new(
+        &config,
+        dictionary.as_ref(),
+        phonotactics.as_ref(),
+        transcription.as_ref(),
+        allophony.as_ref(),
+        &sound_changes,
+        &mut diagnostics,
+    );
+    let dict_page_path = separate_filename_ext(&config.files.dict).0 + ".html";
+
     for (filepath, file) in &mut files {
-        let (filepath_no_ext, ext) = if filepath == "phonet" {
-            ("".to_string(), "phonet")
-        } else {
-            separate_filename_ext(filepath)
-        };
+        // Phonotactic, transcription and allophony rules are loaded separately
+        if [
+            &config.files.phoner,
+            &config.files.transcription,
+            &config.files.allophony,
+        ]
+        .contains(&&*filepath)
+        {
+            continue;
+        }
+        let (filepath_no_ext, ext) = separate_filename_ext(filepath);
 
         match ext {
-            "ling" => {
-                *filepath = filepath_no_ext + ".html";
+            // Macro definitions and partials (`_name.ling`) are not pages
+            "ling" if *filepath == config.files.macros || is_partial(filepath) => continue,
 
-                *file = use_template_html(convert::ling_to_html(file), &template_html, &config);
-                if config.options.minify {
-                    *file = minify_html(file);
+            "ling" => {
+                let (front, file) = split_front_matter(file)
+                    .map_err(|err| MyError(format!("{}: {}", filepath, err)))?;
+                if front.draft && !config.options.drafts {
+                    continue;
                 }
+
+                let file = preprocessor.process(&file, filepath, &mut diagnostics);
+                let path = filepath_no_ext.clone() + ".html";
+                let lookup = dictionary.as_ref().map(|dictionary| Lookup {
+                    dictionary,
+                    gloss_lang: config.gloss_language(),
+                    href: relative_root(&path) + &dict_page_path,
+                });
+                let document = convert::ling_to_html(&file, lookup.as_ref(), phonotactics.as_ref());
+                pages.push(Page {
+                    title: page_title(&front, &document, &filepath_no_ext),
+                    path,
+                    front,
+                    document,
+                    in_nav: true,
+                });
+                continue;
             }
 
-            //TODO
+            // Only dictionary file is used, which is loaded separately
             "ldct" => continue,
-            "llst" => continue,
-            "phonet" => continue,
+
+            // Word lists are pages, except partials which are only included, and concept lists of
+            // coverage page
+            "llst"
+                if is_partial(filepath)
+                    || config
+                        .dictionary
+                        .concept_lists
+                        .iter()
+                        .any(|name| name == filepath) =>
+            {
+                continue
+            }
+            "llst" => {
+                let (front, file) = split_front_matter(file)
+                    .map_err(|err| MyError(format!("{}: {}", filepath, err)))?;
+                if front.draft && !config.options.drafts {
+                    continue;
+                }
+
+                let list = WordList::parse(&file, filepath, &mut diagnostics);
+                let document = convert::ling_to_html(&list.to_ling(), None, None);
+                pages.push(Page {
+                    title: page_title(&front, &document, &filepath_no_ext),
+                    path: filepath_no_ext.clone() + ".html",
+                    front,
+                    document,
+                    in_nav: true,
+                });
+                continue;
+            }
 
             "html" | "css" | "scss" => continue,
 
             _ => return Err(Box::new(MyError("Unknown file type".to_string()))),
         }
+    }
+
+    if let Some(dictionary) = &dictionary {
+        // Reverse dictionaries are in same directory as dictionary
+        let (dict_stem, _) = separate_filename_ext(&config.files.dict);
+        let dict_href = dict_page_path.rsplit('/').next().unwrap_or_default();
+
+        // Entry pages are in directory with name of dictionary, next to dictionary page
+        let entry_dir = dict_stem.rsplit('/').next().unwrap_or_default().to_string() + "/";
+        if config.dictionary.entry_pages {
+            let entry_pages = entry_pages(&config, dictionary, &pages, &dict_stem, dict_href);
+            pages.extend(entry_pages);
+        }
+
+        for (code, name) in config.reverse_languages() {
+            let title = format!("{} ({})", config.dictionary.title, case::upper_first(&name));
+            pages.push(Page {
+                title: title.clone(),
+                path: format!("{}-{}.html", dict_stem, code),
+                front: FrontMatter {
+                    title: Some(title),
+                    layout: config.dictionary.layout.clone(),
+                    ..Default::default()
+                },
+                document: dict::reverse_to_html(
+                    dictionary,
+                    &code,
+                    config.dictionary.stopwords.get(&code).map(Vec::as_slice),
+                    dict_href,
+                ),
+                in_nav: true,
+            });
+        }
+
+        if config.dictionary.stats {
+            let title = format!("{} (Statistics)", config.dictionary.title);
+            let stats = dict::Stats::new(dictionary, &orthography, None, config.gloss_language());
+            pages.push(Page {
+                title: title.clone(),
+                path: format!("{}-stats.html", dict_stem),
+                front: FrontMatter {
+                    title: Some(title),
+                    layout: config.dictionary.layout.clone(),
+                    ..Default::default()
+                },
+                document: stats.to_html(),
+                in_nav: true,
+            });
+        }
+
+        if config.dictionary.minimal_pairs_page {
+            let title = format!("{} (Minimal pairs)", config.dictionary.title);
+            let contrasts = config.dictionary.lint.contrasts();
+            pages.push(Page {
+                title: title.clone(),
+                path: format!("{}-pairs.html", dict_stem),
+                front: FrontMatter {
+                    title: Some(title),
+                    layout: config.dictionary.layout.clone(),
+                    ..Default::default()
+                },
+                document: dict::minimal_pairs_to_html(
+                    &dictionary.minimal_pairs(&contrasts),
+                    &contrasts,
+                    config.gloss_language(),
+                    dict_href,
+                ),
+                in_nav: true,
+            });
+        }
+
+        if config.dictionary.coverage_page {
+            let title = format!("{} (Coverage)", config.dictionary.title);
+            let lists = config
+                .dictionary
+                .concept_lists
+                .iter()
+                .map(|name| {
+                    dict::ConceptList::load(
+                        name,
+                        &config.files.source,
+                        config.gloss_language(),
+                        &mut diagnostics,
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let coverages = lists
+                .iter()
+                .map(|list| {
+                    let stopwords = config.dictionary.stopwords.get(&list.lang);
+                    dictionary.coverage(list, stopwords.map(Vec::as_slice))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            pages.push(Page {
+                title: title.clone(),
+                path: format!("{}-coverage.html", dict_stem),
+                front: FrontMatter {
+                    title: Some(title),
+                    layout: config.dictionary.layout.clone(),
+                    ..Default::default()
+                },
+                document: dict::coverage_to_html(&coverages, dict_href),
+                in_nav: true,
+            });
+        }
 
-        fs::write(format!("{}/{}", config.files.build, filepath), file)
-            .expect("Could not write build file");
+        let mut document = dict::dict_to_html(
+            dictionary,
+            &orthography,
+            config.gloss_language(),
+            &config.package.translations,
+            config.dictionary.entry_pages.then_some(entry_dir.as_str()),
+        );
+        if config.dictionary.search {
+            document.body = format!("{}\n{}", dict::SEARCH_BOX, document.body);
+            write_search_files(&config, dictionary, &orthography, &dict_page_path);
+        }
+
+        pages.push(Page {
+            title: config.dictionary.title.clone(),
+            path: dict_page_path,
+            front: FrontMatter {
+                title: Some(config.dictionary.title.clone()),
+                layout: config.dictionary.layout.clone(),
+                ..Default::default()
+            },
+            document,
+            in_nav: true,
+        });
+    }
+
+    diagnostics.report()?;
+
+    pages.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+
+    for page in &pages {
+        let mut file = match layouts.get(page)? {
+            Some(template) => template.render(&template_context(&config, page, &pages))?,
+            None => format!(
+                "<!DOCTYPE html> <html> <body> {} </body> </html>",
+                page.document.body
+            ),
+        };
+        if config.options.minify {
+            file = minify_html(&file);
+        }
+
+        let path = Path::new(&config.files.build).join(&page.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Could not create build directory");
+        }
+        fs::write(path, file).expect("Could not write build file");
     }
 
     Ok(())
 }
 
+/// Page for each entry of dictionary, with pages which mention entry, in directory `dict_stem`
+///
+/// Entry pages are not in navigation
+fn entry_pages(
+    config: &Config,
+    dictionary: &Dictionary,
+    pages: &[Page],
+    dict_stem: &str,
+    dict_href: &str,
+) -> Vec<Page> {
+    let layout = config
+        .dictionary
+        .entry_layout
+        .clone()
+        .or_else(|| config.dictionary.layout.clone());
+
+    dictionary
+        .entries
+        .iter()
+        .map(|entry| {
+            let path = format!("{}/{}.html", dict_stem, entry.id);
+            let root = relative_root(&path);
+            let mentions = pages
+                .iter()
+                .filter(|page| page.document.mentions.contains(&entry.id))
+                .map(|page| (page.title.clone(), root.clone() + &page.path))
+                .collect::<Vec<_>>();
+
+            Page {
+                title: entry.headword.clone(),
+                front: FrontMatter {
+                    title: Some(entry.headword.clone()),
+                    layout: layout.clone(),
+                    ..Default::default()
+                },
+                document: dict::entry_page_to_html(
+                    entry,
+                    dictionary,
+                    config.gloss_language(),
+                    &config.package.translations,
+                    &mentions,
+                    &format!("../{}", dict_href),
+                ),
+                path,
+                in_nav: false,
+            }
+        })
+        .collect()
+}
+
+/// Write search index and script for dictionary, in same directory as dictionary page
+///
+/// Index is written as JSON, and as script for `file://` pages
+fn write_search_files(
+    config: &Config,
+    dictionary: &Dictionary,
+    orthography: &Orthography,
+    dict_page_path: &str,
+) {
+    let dir = match dict_page_path.rsplit_once('/') {
+        Some((dir, _)) => Path::new(&config.files.build).join(dir),
+        None => Path::new(&config.files.build).to_path_buf(),
+    };
+    let page = dict_page_path.rsplit('/').next().unwrap_or_default();
+    let index = dict::search_index(dictionary, orthography, config.gloss_language(), page);
+
+    fs::create_dir_all(&dir).expect("Could not create build directory");
+    fs::write(
+        dir.join("search-index.js"),
+        dict::search_index_script(&index),
+    )
+    .expect("Could not write search index");
+    fs::write(dir.join("search.js"), dict::SEARCH_SCRIPT).expect("Could not write search script");
+}
+
+/// Parse dictionary file, if it exists
+///
+/// Headwords with letters not in alphabet or which break phonotactic rules, IPA which does not
+/// match transcription of headword, and warnings of `dictionary.lint`, are reported
+fn load_dictionary(
+    config: &Config,
+    orthography: &Orthography,
+    phonotactics: Option<&Phonotactics>,
+    transcription: Option<&Rewrite>,
+    diagnostics: &mut Diagnostics,
+) -> Option<Dictionary> {
+    let dictionary = Dictionary::load(config, diagnostics)?;
+    for entry in &dictionary.entries {
+        orthography.check(&entry.headword, &config.files.dict, entry.line, diagnostics);
+        if let Some(phonotactics) = phonotactics {
+            phonotactics.check_text(
+                &entry.headword,
+                &config.files.dict,
+                entry.line,
+                config.phonotactics.level,
+                diagnostics,
+            );
+        }
+        if let (Some(transcription), Some(ipa)) = (transcription, &entry.ipa) {
+            transcription.check_ipa(
+                &entry.headword,
+                ipa,
+                &config.files.dict,
+                entry.line,
+                config.transcription.level,
+                diagnostics,
+            );
+        }
+    }
+    dictionary.lint(
+        &config.dictionary.lint,
+        config.gloss_language(),
+        &config.files.dict,
+        diagnostics,
+    );
+    Some(dictionary)
+}
+
+/// Whether file is only for including in other files, if filename starts with `_`
+fn is_partial(filepath: &str) -> bool {
+    filepath
+        .rsplit('/')
+        .next()
+        .is_some_and(|filename| filename.starts_with('_'))
+}
+
+/// Converted `.ling` file, with output path
+struct Page {
+    title: String,
+    /// Path of html file, relative to build directory
+    path: String,
+    front: FrontMatter,
+    document: Document,
+    /// Whether page is listed in navigation of template
+    in_nav: bool,
+}
+
+impl Page {
+    /// Relative path from page to root of build directory, such as `./` or `../../`
+    fn root(&self) -> String {
+        relative_root(&self.path)
+    }
+
+    /// Order of page in navigation
+    ///
+    /// Index first, then pages with `order` in front matter, then alphabetical by title
+    fn sort_key(&self) -> (bool, bool, i64, &str) {
+        (
+            self.path != "index.html",
+            self.front.order.is_none(),
+            self.front.order.unwrap_or(0),
+            &self.title,
+        )
+    }
+}
+
+/// Get title of page from front matter, then from first header, then from filename
+fn page_title(front: &FrontMatter, document: &Document, filepath: &str) -> String {
+    if let Some(title) = &front.title {
+        return title.clone();
+    }
+    match document.headings.first() {
+        Some(heading) => heading.text.clone(),
+        None => {
+            let filename = filepath.rsplit('/').next().unwrap_or(filepath);
+            case::upper_first(&filename.replace(['-', '_'], " "))
+        }
+    }
+}
+
+/// Layout templates, loaded when first used
+struct Layouts<'a> {
+    config: &'a Config,
+    /// Template from `files.template`, if exists
+    default: Option<Template>,
+    loaded: HashMap<String, Template>,
+}
+
+impl<'a> Layouts<'a> {
+    fn new(config: &'a Config) -> Result<Self, Box<dyn Error>> {
+        let path = Path::new(&config.files.source).join(&config.files.template);
+        let default = if path.exists() {
+            Some(Template::load(&path)?)
+        } else {
+            None
+        };
+
+        Ok(Layouts {
+            config,
+            default,
+            loaded: HashMap::new(),
+        })
+    }
+
+    /// Get layout for page
+    ///
+    /// Uses layout from front matter, then layout of closest directory in `layouts` config, then default template
+    fn get(&mut self, page: &Page) -> Result<Option<&Template>, Box<dyn Error>> {
+        let name = page.front.layout.as_ref().or_else(|| {
+            self.config
+                .layouts
+                .iter()
+                .filter(|(dir, _)| {
+                    let dir = dir.trim_matches('/');
+                    page.path.starts_with(dir) && page.path[dir.len()..].starts_with('/')
+                })
+                .max_by_key(|(dir, _)| dir.len())
+                .map(|(_, layout)| layout)
+        });
+
+        let name = match name {
+            Some(name) => name,
+            None => return Ok(self.default.as_ref()),
+        };
+
+        if !self.loaded.contains_key(name) {
+            let path = Path::new(&self.config.files.source)
+                .join(&self.config.files.layouts)
+                .join(format!("{}.html", name));
+            if !path.exists() {
+                return Err(Box::new(MyError(format!(
+                    "{}: layout `{}` does not exist",
+                    page.path, name
+                ))));
+            }
+            self.loaded.insert(name.clone(), Template::load(&path)?);
+        }
+
+        Ok(self.loaded.get(name))
+    }
+}
+
+/// Create table of contents for template, from page headers
+fn toc_value(document: &Document) -> Value {
+    let toc = document
+        .headings
+        .iter()
+        .map(|heading| {
+            Value::object([
+                ("level", Value::from(heading.level.to_string())),
+                ("text", Value::from(heading.text.as_str())),
+                ("id", Value::from(heading.id.as_str())),
+            ])
+        })
+        .collect::<Vec<_>>();
+    Value::from(toc)
+}
+
+/// Create values available to template, for one page
+fn template_context(config: &Config, page: &Page, pages: &[Page]) -> Value {
+    let package = &config.package;
+
+    let translations = package
+        .translations
+        .iter()
+        .map(|(code, name)| {
+            Value::object([
+                ("code", Value::from(code.as_str())),
+                ("name", Value::from(name.as_str())),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    let nav = pages
+        .iter()
+        .filter(|other| other.in_nav)
+        .map(|other| {
+            Value::object([
+                ("title", Value::from(other.title.as_str())),
+                ("path", Value::from(other.path.as_str())),
+                ("current", Value::from(other.path == page.path)),
+                ("description", Value::from(other.front.description.clone())),
+                ("tags", Value::from(other.front.tags.clone())),
+                ("toc", toc_value(&other.document)),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    let body = Value::Html(page.document.body.clone());
+    let title = case::upper_first(&package.name);
+
+    Value::object([
+        (
+            "package",
+            Value::object([
+                ("name", Value::from(package.name.as_str())),
+                ("title", Value::from(title.as_str())),
+                ("code", Value::from(package.code.as_str())),
+                ("author", Value::from(package.author.clone())),
+                ("translations", Value::from(translations)),
+            ]),
+        ),
+        (
+            "page",
+            Value::object([
+                ("title", Value::from(page.title.as_str())),
+                ("description", Value::from(page.front.description.clone())),
+                ("path", Value::from(page.path.as_str())),
+                ("root", Value::from(page.root())),
+                ("layout", Value::from(page.front.layout.clone())),
+                ("tags", Value::from(page.front.tags.clone())),
+                ("draft", Value::from(page.front.draft)),
+                ("body", body.clone()),
+                ("toc", toc_value(&page.document)),
+            ]),
+        ),
+        ("nav", Value::from(nav)),
+        (
+            "variables",
+            Value::Object(
+                project_variables(config)
+                    .into_iter()
+                    .map(|(name, value)| (name, Value::from(value)))
+                    .collect(),
+            ),
+        ),
+        (
+            "build",
+            Value::object([
+                ("date", Value::from(utils::today())),
+                ("profile", Value::from(config.profile.clone())),
+                ("flags", Value::from(config.options.flags.clone())),
+            ]),
+        ),
+        // Older template variables
+        ("BODY", body),
+        ("TITLE", Value::from(title)),
+    ])
+}
+
 fn minify_css(file: &str) -> Result<String, Box<dyn Error>> {
     use css_minify::optimizations::{Level, Minifier};
 
-    match Minifier::default().minify(&file, Level::Two) {
+    match Minifier::default().minify(file, Level::Two) {
         Ok(x) => Ok(x),
         Err(err) => Err(Box::new(MyError(err.to_string()))),
     }
@@ -115,7 +695,7 @@ fn minify_html(file: &str) -> String {
     use minify_html::{minify, Cfg};
 
     String::from_utf8_lossy(&minify(
-        &file.as_bytes(),
+        file.as_bytes(),
         &Cfg {
             do_not_minify_doctype: true,
             ..Cfg::default()
@@ -123,20 +703,3 @@ fn minify_html(file: &str) -> String {
     ))
     .to_string()
 }
-
-fn use_template_html(file: String, template: &Option<String>, config: &Config) -> String {
-    if let Some(template) = template {
-        //TODO Use lazy_static for regex
-        let html = Regex::new(r"\{\$\s*BODY\s*\}")
-            .unwrap()
-            .replace_all(template, &file)
-            .to_string();
-        let html = Regex::new(r"\{\$\s*TITLE\s*\}")
-            .unwrap()
-            .replace_all(&html, case::upper_first(&config.package.name))
-            .to_string();
-        html
-    } else {
-        format!("<!DOCTYPE html> <html> <body> {} </body> </html>", file)
-    }
-}
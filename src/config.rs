@@ -1,5 +1,9 @@
+use std::{collections::BTreeMap, error::Error};
+
 use serde::Deserialize;
 
+use crate::{diagnostic::Level, MyError};
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub package: Package,
@@ -7,12 +11,145 @@ pub struct Config {
     pub files: Files,
     #[serde(default)]
     pub options: Options,
+    #[serde(default)]
+    pub dictionary: DictOptions,
+    #[serde(default)]
+    pub orthography: OrthographyConfig,
+    #[serde(default)]
+    pub phonotactics: PhonotacticsOptions,
+    #[serde(default)]
+    pub transcription: TranscriptionOptions,
+    /// Layout name for each directory in source, such as `grammar = "chapter"`
+    #[serde(default)]
+    pub layouts: BTreeMap<String, String>,
+    /// Values for `{$name}` statements in `.ling` files
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    /// Macros for `{!name args}` statements in `.ling` files
+    #[serde(default)]
+    pub macros: BTreeMap<String, MacroConfig>,
+    /// Build profiles, which override `[options]` and `[files]`
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Name of build profile in use
+    #[serde(skip)]
+    pub profile: Option<String>,
+}
+
+/// Build profile, such as
+///
+/// ```toml
+/// [profiles.internal]
+/// flags = ["internal"]
+/// options = { drafts = true }
+/// files = { build = "./build-internal/" }
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Profile {
+    /// Added to `options.flags`
+    #[serde(default)]
+    pub flags: Vec<String>,
+    /// Overrides for `[options]`, checked when profile is used
+    #[serde(default)]
+    pub options: Option<toml::Value>,
+    /// Overrides for `[files]`, checked when profile is used
+    #[serde(default)]
+    pub files: Option<toml::Value>,
+}
+
+/// Macro defined in config, such as
+///
+/// ```toml
+/// [macros.word]
+/// params = ["word", "ipa", "gloss = "]
+/// body = "{' $word} {/ $ipa} *$gloss*"
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct MacroConfig {
+    /// Parameter names, with optional default value after `=`
+    #[serde(default)]
+    pub params: Vec<String>,
+    pub body: String,
 }
 
 impl Config {
     pub fn from(file: &str) -> Result<Self, toml::de::Error> {
         toml::from_str(file)
     }
+
+    /// Language code of glosses in dictionary
+    pub fn gloss_language(&self) -> &str {
+        self.dictionary
+            .gloss_language
+            .as_deref()
+            .or_else(|| {
+                self.package
+                    .translations
+                    .first()
+                    .map(|(code, _)| code.as_str())
+            })
+            .unwrap_or("en")
+    }
+
+    /// Codes of languages in `package.translations`
+    pub fn translation_codes(&self) -> Vec<String> {
+        self.package
+            .translations
+            .iter()
+            .map(|(code, _)| code.clone())
+            .collect()
+    }
+
+    /// Codes and names of languages for reverse dictionary pages
+    pub fn reverse_languages(&self) -> Vec<(String, String)> {
+        if self.package.translations.is_empty() && self.dictionary.reverse.is_none() {
+            let lang = self.gloss_language().to_string();
+            return vec![(lang.clone(), lang)];
+        }
+
+        self.package
+            .translations
+            .iter()
+            .filter(|(code, _)| {
+                self.dictionary
+                    .reverse
+                    .as_ref()
+                    .is_none_or(|reverse| reverse.contains(code))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Parse config, with `[options]` and `[files]` overridden by build profile
+    pub fn from_profile(file: &str, profile: &str) -> Result<Self, Box<dyn Error>> {
+        let mut value: toml::Value = toml::from_str(file)?;
+
+        let overrides = value
+            .get("profiles")
+            .and_then(|profiles| profiles.get(profile))
+            .cloned()
+            .ok_or_else(|| MyError(format!("No profile named `{}`", profile)))?;
+
+        for section in ["options", "files"] {
+            if let Some(toml::Value::Table(overrides)) = overrides.get(section) {
+                if let Some(table) = value.as_table_mut() {
+                    let base = table
+                        .entry(section)
+                        .or_insert_with(|| toml::Value::Table(Default::default()));
+                    if let toml::Value::Table(base) = base {
+                        base.extend(overrides.clone());
+                    }
+                }
+            }
+        }
+
+        let mut config: Config = value.try_into()?;
+        let flags = config.profiles[profile].flags.clone();
+        config.options.flags.extend(flags);
+        config.profile = Some(profile.to_string());
+
+        Ok(config)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -28,11 +165,210 @@ pub struct Package {
 #[serde(default)]
 pub struct Options {
     pub minify: bool,
+    /// Build pages marked as draft in front matter
+    pub drafts: bool,
+    /// Flags for `{?flag ...}` conditional statements in `.ling` files
+    pub flags: Vec<String>,
 }
 
 impl Default for Options {
     fn default() -> Self {
-        Options { minify: true }
+        Options {
+            minify: true,
+            drafts: false,
+            flags: Vec::new(),
+        }
+    }
+}
+
+/// Options for dictionary file and generated dictionary page
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct DictOptions {
+    /// Title of dictionary page
+    pub title: String,
+    /// Layout of dictionary page, instead of default template
+    pub layout: Option<String>,
+    /// Language code of `- gloss` lines, default is first language in `package.translations`
+    pub gloss_language: Option<String>,
+    /// Whether `{% word}` statements with words not in dictionary fail build, or only warn
+    pub missing_words: Level,
+    /// Language codes for reverse dictionary pages, default is every language in
+    /// `package.translations`
+    pub reverse: Option<Vec<String>>,
+    /// Words which are not keywords in reverse dictionary, for each language
+    ///
+    /// Default for `en` is common articles and prepositions
+    pub stopwords: BTreeMap<String, Vec<String>>,
+    /// Columns for `lingdocs dict export` and `lingdocs dict import`
+    pub table: TableOptions,
+    /// Add search box to dictionary page, with search index and script
+    pub search: bool,
+    /// Generate page for each entry, in directory with name of dictionary file
+    pub entry_pages: bool,
+    /// Layout of entry pages, default is layout of dictionary page
+    pub entry_layout: Option<String>,
+    /// Generate page of lexicon statistics, next to dictionary page
+    pub stats: bool,
+    /// Warnings about entries of dictionary
+    pub lint: LintOptions,
+    /// Generate page of minimal pairs for `lint.contrasts`, next to dictionary page
+    pub minimal_pairs_page: bool,
+    /// Concept lists for `lingdocs coverage` and coverage page: `swadesh-100`, `swadesh-207`,
+    /// `leipzig-jakarta`, or `.llst` file in source directory
+    pub concept_lists: Vec<String>,
+    /// Generate page of coverage of concept lists, next to dictionary page
+    pub coverage_page: bool,
+}
+
+/// Warnings about entries of dictionary, such as
+///
+/// ```toml
+/// [dictionary.lint]
+/// homographs = true
+/// minimal_pairs = true
+/// contrasts = ["p b", "s ʃ"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct LintOptions {
+    /// Entries with same headword, IPA and gloss
+    pub duplicates: bool,
+    /// Entries with same IPA, but different headword
+    pub homophones: bool,
+    /// Entries with same headword, but different IPA or gloss
+    pub homographs: bool,
+    /// Entries with IPA which differ in only one phoneme, for `contrasts`
+    pub minimal_pairs: bool,
+    /// Phonemes which contrast, separated with spaces. Default for minimal pairs page is any phonemes
+    pub contrasts: Vec<String>,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        LintOptions {
+            duplicates: true,
+            homophones: true,
+            homographs: false,
+            minimal_pairs: false,
+            contrasts: Vec::new(),
+        }
+    }
+}
+
+impl LintOptions {
+    /// Each pair of phonemes in `contrasts`
+    pub fn contrasts(&self) -> Vec<(String, String)> {
+        let mut pairs = Vec::new();
+        for contrast in &self.contrasts {
+            let phonemes = contrast.split_whitespace().collect::<Vec<_>>();
+            for (i, a) in phonemes.iter().enumerate() {
+                for b in &phonemes[i + 1..] {
+                    pairs.push((a.to_string(), b.to_string()));
+                }
+            }
+        }
+        pairs
+    }
+}
+
+/// Columns of exported dictionary, such as
+///
+/// ```toml
+/// [dictionary.table]
+/// fields = ["headword", "pos", "gloss", "eo"]
+/// columns = { headword = "Word", gloss = "English" }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TableOptions {
+    /// Fields in order, default is all fields
+    pub fields: Vec<String>,
+    /// Column name of each field, default is field name
+    pub columns: BTreeMap<String, String>,
+}
+
+impl Default for DictOptions {
+    fn default() -> Self {
+        DictOptions {
+            title: String::from("Dictionary"),
+            layout: None,
+            gloss_language: None,
+            missing_words: Level::Error,
+            reverse: None,
+            stopwords: BTreeMap::new(),
+            table: TableOptions::default(),
+            search: true,
+            entry_pages: false,
+            entry_layout: None,
+            stats: false,
+            lint: LintOptions::default(),
+            minimal_pairs_page: false,
+            concept_lists: vec![String::from("swadesh-100")],
+            coverage_page: false,
+        }
+    }
+}
+
+/// Alphabet of language, in collation order, such as
+///
+/// ```toml
+/// [orthography]
+/// alphabet = ["a á", "b", "n", "ng", "o"]
+/// ignore = ["-", "'"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct OrthographyConfig {
+    /// Letters, which can be multigraphs. Variants separated with space sort in same position
+    pub alphabet: Vec<String>,
+    /// Characters which are skipped when sorting
+    pub ignore: Vec<String>,
+}
+
+/// Checks of words with phonotactic rules in `files.phoner`, such as
+///
+/// ```toml
+/// [phonotactics]
+/// level = "warning"
+/// pages = true
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct PhonotacticsOptions {
+    /// Whether words which break a rule fail build, or only warn
+    pub level: Level,
+    /// Check words of `{' text}` statements in `.ling` files, as well as headwords of dictionary
+    pub pages: bool,
+}
+
+impl Default for PhonotacticsOptions {
+    fn default() -> Self {
+        PhonotacticsOptions {
+            level: Level::Error,
+            pages: false,
+        }
+    }
+}
+
+/// Checks of IPA with transcription rules in `files.transcription`, such as
+///
+/// ```toml
+/// [transcription]
+/// level = "error"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct TranscriptionOptions {
+    /// Whether IPA which does not match transcription fails build, or only warns
+    pub level: Level,
+}
+
+impl Default for TranscriptionOptions {
+    fn default() -> Self {
+        TranscriptionOptions {
+            level: Level::Warning,
+        }
     }
 }
 
@@ -42,9 +378,20 @@ pub struct Files {
     pub build: String,
     pub source: String,
     pub index: String,
+    /// File of phonotactic rules, in source directory
     pub phoner: String,
+    /// File of rules from spelling to broad IPA, in source directory
+    pub transcription: String,
+    /// File of rules from broad to narrow IPA, in source directory
+    pub allophony: String,
+    /// Directory of sound change files, in source directory
+    pub sound_changes: String,
     pub dict: String,
     pub template: String,
+    /// Directory of layout templates, in source directory
+    pub layouts: String,
+    /// File of macro definitions, in source directory
+    pub macros: String,
     pub style: Option<String>,
 }
 
@@ -55,9 +402,54 @@ impl Default for Files {
             source: String::from("./src/"),
             index: String::from("index.ling"),
             phoner: String::from("phoner"),
-            dict: String::from("dict.dlst"),
+            transcription: String::from("transcription"),
+            allophony: String::from("allophony"),
+            sound_changes: String::from("sound-changes"),
+            dict: String::from("dict.ldct"),
             template: String::from("template.html"),
+            layouts: String::from("layouts"),
+            macros: String::from("macros.ling"),
             style: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_profile_works() {
+        let file = r#"
+            [package]
+            name = "abc"
+            code = "ab"
+
+            [options]
+            minify = false
+            flags = ["a"]
+
+            [files]
+            source = "./source/"
+
+            [profiles.internal]
+            flags = ["b"]
+            options = { drafts = true }
+            files = { build = "./internal/" }
+        "#;
+
+        let config = Config::from(file).unwrap();
+        assert!(!config.options.drafts);
+        assert_eq!(config.options.flags, vec!["a"]);
+
+        let config = Config::from_profile(file, "internal").unwrap();
+        assert!(!config.options.minify);
+        assert!(config.options.drafts);
+        assert_eq!(config.options.flags, vec!["a", "b"]);
+        assert_eq!(config.files.source, "./source/");
+        assert_eq!(config.files.build, "./internal/");
+        assert_eq!(config.profile.as_deref(), Some("internal"));
+
+        assert!(Config::from_profile(file, "missing").is_err());
+    }
+}
@@ -1,9 +1,61 @@
-use html_escape::encode_text as escape_html;
+use std::collections::HashMap;
+
+use html_escape::{decode_html_entities, encode_text as escape_html};
+use lazy_static::lazy_static;
 use regex::Regex;
 
-use crate::{case, utils::separate_filename_ext};
+use crate::{
+    case,
+    dict::Lookup,
+    phonotactics::{self, Phonotactics},
+    utils::separate_filename_ext,
+};
+
+lazy_static! {
+    static ref RE_HEADER: Regex = Regex::new(r"^#+$").expect("Failed to compile header regex");
+}
+
+/// Id of header from its text, with `-2`, `-3` for later headers with same id
+///
+/// `id_counts` is number of earlier headers with each id
+pub fn header_id(text: &str, id_counts: &mut HashMap<String, usize>) -> String {
+    let id = case::kebab_ascii(text.trim());
+    let count = id_counts.entry(id.clone()).or_insert(0);
+    *count += 1;
+
+    if *count > 1 {
+        format!("{}-{}", id, count)
+    } else {
+        id
+    }
+}
+
+/// Converted `.ling` file
+pub struct Document {
+    pub body: String,
+    /// Headers of document, in order, for table of contents
+    pub headings: Vec<Heading>,
+    /// Ids of dictionary entries linked with `{% word}` statements, in order, without duplicates
+    pub mentions: Vec<String>,
+}
+
+/// Header line of document
+pub struct Heading {
+    /// Number of `#` characters
+    pub level: usize,
+    pub text: String,
+    pub id: String,
+}
 
-pub fn ling_to_html(file: &str) -> String {
+/// Convert `.ling` file to html
+///
+/// `lookup` is dictionary for `{% word}` statements, and `phonotactics` is rules for test words of
+/// `{; rule ; words ;}` statements, if there are any
+pub fn ling_to_html(
+    file: &str,
+    lookup: Option<&Lookup>,
+    phonotactics: Option<&Phonotactics>,
+) -> Document {
     use ListType::*;
 
     /// Kind of HTML list
@@ -18,6 +70,9 @@ pub fn ling_to_html(file: &str) -> String {
 
     // Build values
     let mut body = Vec::<String>::new();
+    let mut headings = Vec::<Heading>::new();
+    // Number of headers with each id, to keep ids unique
+    let mut id_counts = HashMap::<String, usize>::new();
     let mut curr_list = NoList;
 
     // Loop lines in file
@@ -41,16 +96,24 @@ pub fn ling_to_html(file: &str) -> String {
         }
 
         // Add tags if token matches
-        //TODO Use lazy_static for instances of Regex::new - Add error message
         //TODO Add classes
         let maybe_push = match token {
             // Header
-            c if Regex::new(r"^#+$").unwrap().is_match(&c) => Some(format!(
-                r#"<h{d} class="header" id="{id}"> {} </h{d}>"#,
-                escape_html(rest),
-                id = case::kebab_ascii(rest.trim()),
-                d = c.len() + 1,
-            )),
+            c if RE_HEADER.is_match(c) => {
+                let heading = Heading {
+                    level: c.len(),
+                    text: rest.trim().to_string(),
+                    id: header_id(rest, &mut id_counts),
+                };
+                let html = format!(
+                    r#"<h{d} class="header" id="{id}"> {} </h{d}>"#,
+                    escape_html(rest),
+                    id = heading.id,
+                    d = heading.level + 1,
+                );
+                headings.push(heading);
+                Some(html)
+            }
 
             // Quote or note
             ">" => Some(format!(
@@ -95,7 +158,11 @@ pub fn ling_to_html(file: &str) -> String {
                 let s = line.trim();
                 if !s.is_empty() {
                     //TODO Fix this
-                    if s.starts_with("{|") && s.ends_with('}') {
+                    let is_phoner_test = s.starts_with("{;")
+                        && s.ends_with('}')
+                        && phonotactics::split_test(&decode_html_entities(&s[2..s.len() - 1]))
+                            .is_some();
+                    if (s.starts_with("{|") && s.ends_with('}')) || is_phoner_test {
                         // For tables
                         // Don't wrap in p tags
                         Some(escape_html(s).to_string())
@@ -114,15 +181,18 @@ pub fn ling_to_html(file: &str) -> String {
         }
     }
 
-    let body = format_statements(&body.join("\n"));
-
-    let body = format_primatives(&body);
+    let mut mentions = Vec::new();
+    let body = format_statements(&body.join("\n"), lookup, phonotactics, &mut mentions);
 
-    body
+    Document {
+        body: format_primatives(&body),
+        headings,
+        mentions,
+    }
 }
 
 #[derive(Debug)]
-enum Format {
+pub enum Format {
     Text(String),
     Link(String),
     BroadIPA,
@@ -132,6 +202,8 @@ enum Format {
     /// For tables
     HeaderBold,
     Replace,
+    /// Word from dictionary
+    Lookup,
     Comment,
     Unknown,
 }
@@ -153,6 +225,7 @@ impl Format {
             '|' => Table,
             '^' => HeaderBold,
             '$' => Replace,
+            '%' => Lookup,
             '#' => Comment,
             _ => return None,
         })
@@ -216,16 +289,18 @@ impl Format {
             ),
 
             Phoner => {
+                // Unescaped, so semicolon of `&gt;` is not removed
+                let string = decode_html_entities(string);
                 let string = string.trim();
 
                 format!(
                     r#"<code class="phoner"> {} </code>"#,
                     // Remove semicolon if is last character
-                    if string.ends_with(';') {
+                    escape_primatives(&escape_html(if string.ends_with(';') {
                         remove_last_char(string).trim()
                     } else {
                         string
-                    }
+                    }))
                 )
             }
 
@@ -235,33 +310,46 @@ impl Format {
                 format!(r#"<b> {} </b>"#, string)
             }
 
-            // Double $ to not confuse regex later
-            Replace => format!("{{$${}}}", string),
+            // Variables are replaced before conversion
+            // Only escaped `{$$name}` statements are left, which are shown literally
+            Replace => format!("{{${}}}", string),
+
+            // Linked to dictionary in `format_statements`, if there is a dictionary
+            Lookup => Text(String::new()).format(string),
 
             Comment => String::new(),
-            Unknown => format!("{}", string),
+            Unknown => string.to_string(),
         }
     }
 }
 
 /// Format link
 ///
-/// Replaces `.ling` with `.html` file extension
+/// Replaces `.ling` with `.html` file extension, keeping any `#id` after it
 fn format_link(link: &str) -> String {
+    let (link, id) = match link.find('#') {
+        Some(pos) => link.split_at(pos),
+        None => (link, ""),
+    };
     let (filename, mut ext) = separate_filename_ext(link);
 
     if ext.is_empty() {
-        return filename;
+        return filename + id;
     }
 
     if ext == "ling" {
         ext = "html";
     }
 
-    filename + "." + ext
+    filename + "." + ext + id
 }
 
-fn format_statements(body: &str) -> String {
+fn format_statements(
+    body: &str,
+    lookup: Option<&Lookup>,
+    phonotactics: Option<&Phonotactics>,
+    mentions: &mut Vec<String>,
+) -> String {
     let mut curr_statement: Option<Format> = None;
     let mut curr_statement_building = false;
 
@@ -272,7 +360,11 @@ fn format_statements(body: &str) -> String {
 
     for ch in body.chars() {
         if is_escaped {
-            output.push(ch);
+            // Kept after backslash in statement, for primatives
+            match &mut statement {
+                Some(stat) => stat.push(ch),
+                None => output.push(ch),
+            }
         } else {
             match ch {
                 '{' if statement.is_none() => {
@@ -285,9 +377,25 @@ fn format_statements(body: &str) -> String {
                     let stat = stat.trim();
 
                     if let Some(curr_statement) = &curr_statement {
-                        output.push_str(&curr_statement.format(stat));
+                        output.push_str(&match (curr_statement, lookup, phonotactics) {
+                            (Format::Phoner, _, Some(phonotactics))
+                                if phonotactics::split_test(&decode_html_entities(stat))
+                                    .is_some() =>
+                            {
+                                format_phoner_test(stat, phonotactics)
+                            }
+                            (Format::Lookup, Some(lookup), _) => {
+                                if let Some(entry) = lookup.find(stat) {
+                                    if !mentions.contains(&entry.id) {
+                                        mentions.push(entry.id.clone());
+                                    }
+                                }
+                                lookup.to_html(stat)
+                            }
+                            _ => curr_statement.format(stat),
+                        });
                     } else {
-                        output.push_str(&stat);
+                        output.push_str(stat);
                     }
 
                     statement = None;
@@ -296,7 +404,7 @@ fn format_statements(body: &str) -> String {
 
                 _ => {
                     if let Some(stat) = &mut statement {
-                        if let None = curr_statement {
+                        if curr_statement.is_none() {
                             curr_statement_building = true;
                             curr_statement = Some(Format::new(ch));
                         } else {
@@ -324,16 +432,74 @@ fn format_statements(body: &str) -> String {
         }
 
         // Escape next character
-        if ch == '\\' && !is_escaped {
-            is_escaped = true;
-        } else {
-            is_escaped = false;
-        }
+        is_escaped = ch == '\\' && !is_escaped;
     }
 
     output
 }
 
+/// Format `{; rule ; words ;}` statement as rule, with table of whether each word follows
+/// phonotactic rules, and which rule it breaks
+///
+/// Statement is HTML-escaped already
+fn format_phoner_test(statement: &str, phonotactics: &Phonotactics) -> String {
+    let statement = decode_html_entities(statement);
+    let (rule, words) = phonotactics::split_test(&statement).unwrap_or_default();
+    let rows = words
+        .iter()
+        .map(|word| {
+            let broken = phonotactics.check(word.trim_start_matches('*'));
+            let (class, result, reason) = match broken {
+                Some(rule) => (
+                    "fail",
+                    "✗",
+                    format!(
+                        "{} {}",
+                        Format::Phoner.format(&rule.to_string()),
+                        escape_primatives(&escape_html(
+                            rule.description.as_deref().unwrap_or_default()
+                        ))
+                    ),
+                ),
+                None => ("pass", "✓", String::new()),
+            };
+            format!(
+                "  <tr class=\"row {}\">\n\
+                    <td class=\"cell body\"> {} </td>\n\
+                    <td class=\"cell body result\"> {} </td>\n\
+                    <td class=\"cell body\"> {} </td>\n  \
+                </tr>",
+                class,
+                Format::Text(String::new()).format(&escape_primatives(&escape_html(word))),
+                result,
+                reason.trim()
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let table = format!(
+        "\n<table class=\"table phoner-test\">\n{}\n</table>\n",
+        rows.join("\n")
+    );
+    if rule.is_empty() {
+        table
+    } else {
+        Format::Phoner.format(rule) + &table
+    }
+}
+
+/// Escape characters which are formatted after statements, such as `*` for italics
+pub fn escape_primatives(text: &str) -> String {
+    let mut output = String::new();
+    for ch in text.chars() {
+        if matches!(ch, '*' | '^' | '_' | '~' | '\\') {
+            output.push('\\');
+        }
+        output.push(ch);
+    }
+    output
+}
+
 /// Format table from string
 fn format_table(text: &str) -> String {
     // Build variables
@@ -370,9 +536,9 @@ fn format_table(text: &str) -> String {
                 ));
             } else {
                 // Body
-                // Format from header
+                // Format from header, except for empty cells
                 let text = match formats.get(col_num) {
-                    Some(Some(format)) => format.format(cell),
+                    Some(Some(format)) if !cell.trim().is_empty() => format.format(cell),
                     _ => cell.to_string(),
                 };
 
@@ -473,11 +639,7 @@ fn format_primatives(body: &str) -> String {
         }
 
         // Escape next character
-        if ch == '\\' && !is_escaped {
-            is_escaped = true;
-        } else {
-            is_escaped = false;
-        }
+        is_escaped = ch == '\\' && !is_escaped;
     }
 
     output
@@ -529,7 +691,79 @@ fn remove_last_char(s: &str) -> &str {
 
 #[cfg(test)]
 mod tests {
-    use super::{remove_last_char as rlc, split_lines_preserve_statements as slps};
+    use super::{
+        format_link, ling_to_html, remove_last_char as rlc,
+        split_lines_preserve_statements as slps, Lookup, Phonotactics,
+    };
+    use crate::{diagnostic::Diagnostics, dict::Dictionary};
+
+    #[test]
+    fn header_ids_are_unique() {
+        let ids = ling_to_html("# abc\n## def\n# abc\n# abc", None, None)
+            .headings
+            .into_iter()
+            .map(|heading| heading.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["abc", "def", "abc-2", "abc-3"]);
+    }
+
+    #[test]
+    fn lookup_links_to_dictionary() {
+        let dictionary = Dictionary::parse(
+            "kata\n  pos: noun\n  - cat\n  - kitten",
+            "dict.ldct",
+            "en",
+            &[],
+            &mut Diagnostics::default(),
+        );
+        let lookup = Lookup {
+            dictionary: &dictionary,
+            gloss_lang: "en",
+            href: "../dict.html".to_string(),
+        };
+
+        let document = ling_to_html("{% kata} {% pita} {% Kata}", Some(&lookup), None);
+        assert_eq!(document.mentions, vec!["kata"]);
+        let body = document.body;
+        assert!(body.contains(
+            r#"<a class="link lookup" href="../dict.html#kata" title="noun: cat; kitten">"#
+        ));
+        assert_eq!(body.matches("<a ").count(), 2);
+    }
+
+    #[test]
+    fn phoner_test_shows_results() {
+        let phonotactics = Phonotactics::parse(
+            "V = a i\n# No vowel clusters\n! <V> <V>",
+            "phoner",
+            &mut Diagnostics::default(),
+        );
+
+        let body = ling_to_html("{; ! <V> <V> ; kata *kaat ;}", None, Some(&phonotactics)).body;
+        assert!(body.starts_with(r#"<code class="phoner"> ! &lt;V&gt; &lt;V&gt; </code>"#));
+        assert_eq!(body.matches(r#"<tr class="row pass">"#).count(), 1);
+        assert_eq!(body.matches(r#"<tr class="row fail">"#).count(), 1);
+        assert!(body.contains("*kaat"));
+        assert!(body.contains("No vowel clusters"));
+
+        let body = ling_to_html("{; ! <V> <V> ; kata ;}", None, None).body;
+        assert!(!body.contains("<table"));
+    }
+
+    #[test]
+    fn escapes_work_in_statements() {
+        let body = ling_to_html("{' \\*kata \\~ kat}", None, None).body;
+        assert!(body.contains("*kata ~ kat"), "{}", body);
+        assert!(!body.contains("<i"));
+    }
+
+    #[test]
+    fn format_link_works() {
+        assert_eq!(format_link("./other.ling"), "./other.html");
+        assert_eq!(format_link("../other.ling#id"), "../other.html#id");
+        assert_eq!(format_link("#id"), "#id");
+        assert_eq!(format_link("https://example.com"), "https://example.com");
+    }
 
     #[test]
     fn remove_last_char_works() {
@@ -0,0 +1,130 @@
+use std::{error::Error, fmt};
+
+use serde::Deserialize;
+
+use crate::MyError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    /// Reported, but does not fail build
+    Warning,
+}
+
+/// Problem found in a source file
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub level: Level,
+    /// Path of file, relative to source directory
+    pub file: String,
+    /// Line number, starting at 1, if known
+    pub line: Option<usize>,
+    pub message: String,
+    /// Extra information, such as another related location
+    pub note: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(file: &str, line: Option<usize>, message: impl Into<String>) -> Self {
+        Diagnostic {
+            level: Level::Error,
+            file: file.to_string(),
+            line,
+            message: message.into(),
+            note: None,
+        }
+    }
+
+    pub fn warning(file: &str, line: Option<usize>, message: impl Into<String>) -> Self {
+        Diagnostic {
+            level: Level::Warning,
+            ..Diagnostic::error(file, line, message)
+        }
+    }
+
+    /// Error or warning, from level in config
+    pub fn new(level: Level, file: &str, line: Option<usize>, message: impl Into<String>) -> Self {
+        Diagnostic {
+            level,
+            ..Diagnostic::error(file, line, message)
+        }
+    }
+
+    pub fn with_note(self, note: impl Into<String>) -> Self {
+        Diagnostic {
+            note: Some(note.into()),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+        match self.line {
+            Some(line) => write!(f, "{}: {}:{}: {}", level, self.file, line, self.message)?,
+            None => write!(f, "{}: {}: {}", level, self.file, self.message)?,
+        }
+        if let Some(note) = &self.note {
+            write!(f, "\n  note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+/// Diagnostics collected during build
+#[derive(Debug, Default)]
+pub struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.0.push(diagnostic);
+    }
+
+    /// Number of diagnostics, to give start of [`Diagnostics::map_lines`]
+    pub fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Change lines of diagnostics of file, after first `start` diagnostics
+    pub fn map_lines(&mut self, start: usize, file: &str, map: impl Fn(usize) -> usize) {
+        for diagnostic in self.0.iter_mut().skip(start) {
+            if diagnostic.file == file {
+                diagnostic.line = diagnostic.line.map(&map);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.0.iter()
+    }
+
+    /// Print all diagnostics, and clear them
+    ///
+    /// Returns error if there were any errors, not only warnings
+    pub fn report(&mut self) -> Result<(), Box<dyn Error>> {
+        let diagnostics = std::mem::take(&mut self.0);
+        for diagnostic in &diagnostics {
+            eprintln!("{}", diagnostic);
+        }
+
+        let errors = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.level == Level::Error)
+            .count();
+        if errors > 0 {
+            return Err(Box::new(MyError(format!(
+                "Build failed with {} error{}",
+                errors,
+                if errors == 1 { "" } else { "s" }
+            ))));
+        }
+
+        Ok(())
+    }
+}
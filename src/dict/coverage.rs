@@ -0,0 +1,390 @@
+use std::{fmt, fs};
+
+use html_escape::encode_text as escape_html;
+
+use super::{
+    reverse::{language_stopwords, phrases},
+    slug, Dictionary, Entry, Sense,
+};
+use crate::{
+    convert::{Document, Format, Heading},
+    diagnostic::Diagnostics,
+    front_matter::split_front_matter,
+    word_list::{Column, WordList},
+    MyError,
+};
+
+/// Concept lists in binary, by name
+const BUILTIN_LISTS: &[(&str, &str)] = &[
+    ("swadesh-100", include_str!("concepts/swadesh-100.llst")),
+    ("swadesh-207", include_str!("concepts/swadesh-207.llst")),
+    (
+        "leipzig-jakarta",
+        include_str!("concepts/leipzig-jakarta.llst"),
+    ),
+];
+
+/// List of concepts which a lexicon should have words for, such as Swadesh list
+pub struct ConceptList {
+    pub title: String,
+    /// Language of concepts, matched with glosses in same language
+    pub lang: String,
+    pub concepts: Vec<Concept>,
+}
+
+/// Concept of list, with alternatives separated by `/`, such as `flesh/meat`
+pub struct Concept {
+    pub gloss: String,
+    pub note: Option<String>,
+}
+
+/// Entries of dictionary for each concept of list
+pub struct Coverage<'a> {
+    pub title: String,
+    /// Concepts in order of list, with entries which have a sense with keyword of concept
+    pub concepts: Vec<(&'a Concept, Vec<&'a Entry>)>,
+}
+
+impl ConceptList {
+    /// Built-in list with name, in English, or `.llst` file in source directory
+    ///
+    /// Language of `.llst` file is `lang` of its front matter, or `default_lang`
+    pub fn load(
+        name: &str,
+        source: &str,
+        default_lang: &str,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Self, MyError> {
+        if let Some((_, file)) = BUILTIN_LISTS.iter().find(|(builtin, _)| *builtin == name) {
+            let list = WordList::parse(file, name, diagnostics);
+            return Ok(ConceptList::from_word_list(&list, name, "en"));
+        }
+        if !name.ends_with(".llst") {
+            let names = BUILTIN_LISTS
+                .iter()
+                .map(|(name, _)| format!("`{}`", name))
+                .collect::<Vec<_>>();
+            return Err(MyError(format!(
+                "Unknown concept list `{}`, use {} or a `.llst` file",
+                name,
+                names.join(", ")
+            )));
+        }
+
+        let file = fs::read_to_string(format!("{}/{}", source, name))
+            .map_err(|err| MyError(format!("Could not read concept list `{}`: {}", name, err)))?;
+        let (front, file) =
+            split_front_matter(&file).map_err(|err| MyError(format!("{}: {}", name, err)))?;
+        let list = WordList::parse(&file, name, diagnostics);
+        let lang = front.lang.as_deref().unwrap_or(default_lang);
+        Ok(ConceptList::from_word_list(&list, name, lang))
+    }
+
+    /// Concepts from `gloss` column of word list, or first column, with notes from `note` column
+    ///
+    /// Title is title of word list, or `name`, and `lang` is language of concepts
+    pub fn from_word_list(list: &WordList, name: &str, lang: &str) -> Self {
+        let column = |column: &Column| list.columns.iter().position(|other| other == column);
+        let gloss = column(&Column::Gloss).unwrap_or(0);
+        let note = column(&Column::Note);
+        let cell = |row: &[String], i: usize| row.get(i).filter(|text| !text.is_empty()).cloned();
+
+        ConceptList {
+            title: list.title.clone().unwrap_or_else(|| name.to_string()),
+            lang: lang.to_string(),
+            concepts: list
+                .rows
+                .iter()
+                .filter_map(|row| {
+                    Some(Concept {
+                        gloss: cell(row, gloss)?,
+                        note: note.and_then(|note| cell(row, note)),
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Dictionary {
+    /// Entries for each concept of list, matched by phrases of glosses in language of list
+    ///
+    /// A concept matches an entry if any alternative of the concept is a whole phrase of a gloss, or
+    /// a `key` of a sense, without stopwords, so `small` does not match `small feline`. Fails if no sense has a
+    /// gloss or `key` in language of list
+    pub fn coverage<'a>(
+        &'a self,
+        list: &'a ConceptList,
+        stopwords: Option<&[String]>,
+    ) -> Result<Coverage<'a>, MyError> {
+        let gloss_lang = list.lang.as_str();
+        let has_lang = self
+            .entries
+            .iter()
+            .flat_map(|entry| &entry.senses)
+            .any(|sense| {
+                sense
+                    .glosses
+                    .iter()
+                    .chain(&sense.keys)
+                    .any(|gloss| gloss.lang == gloss_lang)
+            });
+        if !has_lang {
+            return Err(MyError(format!(
+                "Dictionary has no glosses in `{}`, the language of concept list `{}`",
+                gloss_lang, list.title
+            )));
+        }
+
+        let stopwords = language_stopwords(gloss_lang, stopwords);
+        let entry_keys = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let keys = entry
+                    .senses
+                    .iter()
+                    .flat_map(|sense| sense_phrases(sense, gloss_lang, &stopwords))
+                    .collect::<Vec<_>>();
+                (entry, keys)
+            })
+            .collect::<Vec<_>>();
+
+        let concepts = list
+            .concepts
+            .iter()
+            .map(|concept| {
+                let concept_keys = concept
+                    .gloss
+                    .split('/')
+                    .flat_map(|alternative| key_phrases(alternative, &stopwords))
+                    .collect::<Vec<_>>();
+                let entries = entry_keys
+                    .iter()
+                    .filter(|(_, keys)| keys.iter().any(|key| concept_keys.contains(key)))
+                    .map(|(entry, _)| *entry)
+                    .collect();
+                (concept, entries)
+            })
+            .collect();
+
+        Ok(Coverage {
+            title: list.title.clone(),
+            concepts,
+        })
+    }
+}
+
+/// Phrases of gloss and explicit keys of sense in language, without stopwords
+fn sense_phrases(sense: &Sense, lang: &str, stopwords: &[&str]) -> Vec<String> {
+    let mut phrases = key_phrases(sense.gloss(lang).unwrap_or_default(), stopwords);
+    for key in sense.keys.iter().filter(|key| key.lang == lang) {
+        phrases.extend(key_phrases(&key.text, stopwords));
+    }
+    phrases
+}
+
+/// Phrases of gloss, without stopwords, such as `cat` and `small feline` from `cat, a small feline`
+fn key_phrases(gloss: &str, stopwords: &[&str]) -> Vec<String> {
+    phrases(gloss, stopwords)
+        .iter()
+        .map(|words| words.join(" "))
+        .collect()
+}
+
+impl Coverage<'_> {
+    /// Number of concepts with at least one entry
+    pub fn covered(&self) -> usize {
+        self.concepts
+            .iter()
+            .filter(|(_, entries)| !entries.is_empty())
+            .count()
+    }
+
+    /// Concepts without entries, in order of list
+    pub fn missing(&self) -> impl Iterator<Item = &Concept> {
+        self.concepts
+            .iter()
+            .filter(|(_, entries)| entries.is_empty())
+            .map(|(concept, _)| *concept)
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{} of {} concepts ({:.1}%)",
+            self.covered(),
+            self.concepts.len(),
+            self.covered() as f64 * 100.0 / self.concepts.len().max(1) as f64
+        )
+    }
+}
+
+impl fmt::Display for Coverage<'_> {
+    /// Coverage as text, with concepts without entries, for terminal
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.title, self.summary())?;
+        let mut missing = self.missing().peekable();
+        if missing.peek().is_some() {
+            writeln!(f, "Missing:")?;
+        }
+        for concept in missing {
+            match &concept.note {
+                Some(note) => writeln!(f, "  {} ({})", concept.gloss, note)?,
+                None => writeln!(f, "  {}", concept.gloss)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Render coverage of concept lists as page, with a table for each list
+///
+/// `href` is path of dictionary page, relative to page
+pub fn coverage_to_html(coverages: &[Coverage], href: &str) -> Document {
+    let mut body = Vec::new();
+    let mut headings = Vec::new();
+
+    for coverage in coverages {
+        let heading = Heading {
+            level: 1,
+            text: coverage.title.clone(),
+            id: slug(&coverage.title),
+        };
+        body.push(format!(
+            r#"<h2 class="header" id="{}"> {} </h2>"#,
+            heading.id,
+            escape_html(&heading.text)
+        ));
+        headings.push(heading);
+        body.push(format!(
+            "<p class=\"coverage-summary\"> {} </p>",
+            coverage.summary()
+        ));
+
+        body.push("<table class=\"coverage\">".to_string());
+        for (concept, entries) in &coverage.concepts {
+            let note = concept
+                .note
+                .as_ref()
+                .map(|note| format!(" <span class=\"note\"> {} </span>", escape_html(note)))
+                .unwrap_or_default();
+            let links = entries
+                .iter()
+                .map(|entry| {
+                    format!(
+                        r#"<a class="link" href="{}#{}"> {} </a>"#,
+                        href,
+                        entry.id,
+                        Format::Text(String::new()).format(&escape_html(&entry.headword))
+                    )
+                })
+                .collect::<Vec<_>>();
+            body.push(format!(
+                "<tr{}> <td> {}{} </td> <td> {} </td> </tr>",
+                if entries.is_empty() {
+                    " class=\"missing\""
+                } else {
+                    ""
+                },
+                escape_html(&concept.gloss),
+                note,
+                links.join(", ")
+            ));
+        }
+        body.push("</table>".to_string());
+    }
+
+    Document {
+        body: body.join("\n"),
+        headings,
+        mentions: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_lists_work() {
+        for (name, length) in [
+            ("swadesh-100", 100),
+            ("swadesh-207", 207),
+            ("leipzig-jakarta", 100),
+        ] {
+            let mut diagnostics = Diagnostics::default();
+            let list = ConceptList::load(name, ".", "eo", &mut diagnostics).unwrap();
+            assert!(diagnostics.report().is_ok());
+            assert_eq!(list.concepts.len(), length);
+            assert_eq!(list.lang, "en");
+        }
+        assert!(ConceptList::load("swadesh", ".", "en", &mut Diagnostics::default()).is_err());
+    }
+
+    #[test]
+    fn coverage_works() {
+        let mut diagnostics = Diagnostics::default();
+        let dict = Dictionary::parse(
+            "kata\n  - cat, small feline\nmila\n  - to love\nkarno\n  - meat\nmano\n  - arm\n  - hand\nbruli\n  - fire\n    key: burn\n",
+            "dict.ldct",
+            "en",
+            &[],
+            &mut diagnostics,
+        );
+        let list = ConceptList::from_word_list(
+            &WordList::parse(
+                "gloss | note\nfire | noun\nflesh/meat |\nto love |\nhand |\ndog |\nto burn |\nsmall |\ncat |",
+                "concepts.llst",
+                &mut diagnostics,
+            ),
+            "concepts.llst",
+            "en",
+        );
+        assert!(diagnostics.report().is_ok());
+
+        let coverage = dict.coverage(&list, None).unwrap();
+        assert_eq!(
+            coverage
+                .concepts
+                .iter()
+                .map(|(concept, entries)| (
+                    concept.gloss.as_str(),
+                    entries
+                        .iter()
+                        .map(|entry| entry.headword.as_str())
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("fire", vec!["bruli"]),
+                ("flesh/meat", vec!["karno"]),
+                ("to love", vec!["mila"]),
+                ("hand", vec!["mano"]),
+                ("dog", vec![]),
+                ("to burn", vec!["bruli"]),
+                ("small", vec![]),
+                ("cat", vec!["kata"]),
+            ]
+        );
+        assert_eq!(
+            coverage.to_string(),
+            "concepts.llst: 6 of 8 concepts (75.0%)\nMissing:\n  dog\n  small\n"
+        );
+    }
+
+    #[test]
+    fn coverage_needs_glosses_in_language() {
+        let mut diagnostics = Diagnostics::default();
+        let dict = Dictionary::parse("kata\n  - kato\n", "dict.ldct", "eo", &[], &mut diagnostics);
+        let list = ConceptList::load("swadesh-100", ".", "eo", &mut diagnostics).unwrap();
+        assert!(diagnostics.report().is_ok());
+
+        assert_eq!(
+            dict.coverage(&list, None).err().map(|err| err.to_string()),
+            Some(
+                "Dictionary has no glosses in `en`, the language of concept list `Swadesh 100`"
+                    .to_string()
+            )
+        );
+    }
+}
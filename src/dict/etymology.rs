@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use super::{Dictionary, Entry};
+use crate::diagnostic::{Diagnostic, Diagnostics};
+
+/// Origin of entry, from `root`, `from`, `compound` and `borrowed` fields
+///
+/// Words are headwords, or ids of entries, such as `kata-2` for second entry with headword `kata`
+///
+/// ```txt
+/// katisto
+///   root: kata
+///   from: kati
+///   compound: kata, isto
+///   borrowed: Esperanto kato
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Etymology {
+    /// Headword or id of root of entry
+    pub root: Option<String>,
+    /// Headword or id of entry which this entry is derived from
+    pub from: Option<String>,
+    /// Headwords or ids of parts of compound
+    pub compound: Vec<String>,
+    /// Word in other language, which is not in dictionary
+    pub borrowed: Option<String>,
+}
+
+impl Etymology {
+    /// Headwords or ids of entries which this entry derives from, with name of field
+    pub fn sources(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        let root = self.root.iter().map(|word| ("root", word.as_str()));
+        let from = self.from.iter().map(|word| ("from", word.as_str()));
+        let compound = self.compound.iter().map(|word| ("compound", word.as_str()));
+        root.chain(from).chain(compound)
+    }
+}
+
+impl Dictionary {
+    /// Entry which word of etymology field refers to, by id, or else by headword
+    pub fn source(&self, word: &str) -> Option<&Entry> {
+        self.source_position(word).map(|i| &self.entries[i])
+    }
+
+    fn source_position(&self, word: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.id == word)
+            .or_else(|| self.position(word))
+    }
+
+    /// Entries which derive from entry, in order of dictionary
+    pub fn derived(&self, entry: &Entry) -> Vec<&Entry> {
+        self.derived
+            .get(&entry.id)
+            .map(|derived| derived.iter().map(|&i| &self.entries[i]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Indexes of entries which derive from each entry, by id of entry
+    pub(super) fn index_derived(&self) -> HashMap<String, Vec<usize>> {
+        // First entry with each id, headword, and lowercase headword, as in `source`
+        let mut ids = HashMap::<&str, usize>::new();
+        let mut headwords = HashMap::<&str, usize>::new();
+        let mut lowercase = HashMap::<String, usize>::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            ids.entry(&entry.id).or_insert(i);
+            headwords.entry(&entry.headword).or_insert(i);
+            lowercase.entry(entry.headword.to_lowercase()).or_insert(i);
+        }
+
+        let mut derived = HashMap::<String, Vec<usize>>::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            for (_, word) in entry.etymology.sources() {
+                let source = ids
+                    .get(word)
+                    .or_else(|| headwords.get(word))
+                    .or_else(|| lowercase.get(&word.to_lowercase()));
+                if let Some(&source) = source {
+                    let derived = derived.entry(self.entries[source].id.clone()).or_default();
+                    if !derived.contains(&i) {
+                        derived.push(i);
+                    }
+                }
+            }
+        }
+        derived
+    }
+
+    /// Derivation graph of dictionary, in Graphviz DOT format
+    ///
+    /// Edges go from source to derived entry, labelled with field. Borrowed words are boxes, and
+    /// entries without etymology are not included
+    pub fn etymology_dot(&self) -> String {
+        let mut nodes = Vec::<String>::new();
+        let mut edges = Vec::<String>::new();
+        let mut add_node = |id: &str, attributes: String| {
+            let node = format!("  {} [{}];", quote(id), attributes);
+            if !nodes.contains(&node) {
+                nodes.push(node);
+            }
+        };
+
+        for entry in &self.entries {
+            for (field, word) in entry.etymology.sources() {
+                let Some(source) = self.source(word) else {
+                    continue;
+                };
+                add_node(&source.id, format!("label={}", quote(&source.headword)));
+                add_node(&entry.id, format!("label={}", quote(&entry.headword)));
+                edges.push(format!(
+                    "  {} -> {} [label={}];",
+                    quote(&source.id),
+                    quote(&entry.id),
+                    field
+                ));
+            }
+
+            if let Some(borrowed) = &entry.etymology.borrowed {
+                let id = format!("borrowed:{}", borrowed);
+                add_node(&id, format!("label={}, shape=box", quote(borrowed)));
+                add_node(&entry.id, format!("label={}", quote(&entry.headword)));
+                edges.push(format!(
+                    "  {} -> {} [label=borrowed];",
+                    quote(&id),
+                    quote(&entry.id)
+                ));
+            }
+        }
+
+        let mut lines = vec!["digraph etymology {".to_string()];
+        lines.extend(nodes);
+        lines.extend(edges);
+        lines.push("}".to_string());
+        lines.join("\n") + "\n"
+    }
+
+    /// Report etymology fields with words not in dictionary, or with headword of several entries,
+    /// and entries which derive from themselves
+    pub(super) fn check_etymology(&self, filepath: &str, diagnostics: &mut Diagnostics) {
+        // Indexes of sources of each entry
+        let mut graph = Vec::<Vec<usize>>::new();
+        for entry in &self.entries {
+            let mut sources = Vec::new();
+            for (field, word) in entry.etymology.sources() {
+                let homographs = self.homographs(word);
+                if homographs.len() > 1 {
+                    diagnostics.push(Diagnostic::warning(
+                        filepath,
+                        Some(entry.line),
+                        format!(
+                            "word `{}` in `{}` of `{}` is headword of several entries, use id of entry: {}",
+                            word,
+                            field,
+                            entry.headword,
+                            homographs
+                                .iter()
+                                .map(|other| format!("`{}`", other.id))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    ));
+                }
+                match self.source_position(word) {
+                    Some(source) => sources.push(source),
+                    None => diagnostics.push(Diagnostic::error(
+                        filepath,
+                        Some(entry.line),
+                        format!(
+                            "word `{}` in `{}` of `{}` is not in dictionary",
+                            word, field, entry.headword
+                        ),
+                    )),
+                }
+            }
+            graph.push(sources);
+        }
+
+        let mut state = vec![State::Unvisited; graph.len()];
+        for i in 0..graph.len() {
+            let mut stack = Vec::new();
+            self.find_cycles(i, &graph, &mut state, &mut stack, filepath, diagnostics);
+        }
+    }
+
+    /// Entries with headword, ignoring case if no headword matches exactly
+    fn homographs(&self, word: &str) -> Vec<&Entry> {
+        let exact = self
+            .entries
+            .iter()
+            .filter(|entry| entry.headword == word)
+            .collect::<Vec<_>>();
+        if !exact.is_empty() {
+            return exact;
+        }
+        let word = word.to_lowercase();
+        self.entries
+            .iter()
+            .filter(|entry| entry.headword.to_lowercase() == word)
+            .collect()
+    }
+
+    /// Depth-first search from entry, reporting each cycle once
+    fn find_cycles(
+        &self,
+        i: usize,
+        graph: &[Vec<usize>],
+        state: &mut [State],
+        stack: &mut Vec<usize>,
+        filepath: &str,
+        diagnostics: &mut Diagnostics,
+    ) {
+        if state[i] != State::Unvisited {
+            return;
+        }
+        state[i] = State::Visiting;
+        stack.push(i);
+
+        for &source in &graph[i] {
+            match state[source] {
+                State::Unvisited => {
+                    self.find_cycles(source, graph, state, stack, filepath, diagnostics)
+                }
+                State::Visiting => {
+                    let start = stack.iter().position(|&j| j == source).unwrap_or(0);
+                    let cycle = stack[start..]
+                        .iter()
+                        .chain([&source])
+                        .map(|&j| self.entries[j].headword.as_str())
+                        .collect::<Vec<_>>();
+                    let entry = &self.entries[source];
+                    diagnostics.push(Diagnostic::error(
+                        filepath,
+                        Some(entry.line),
+                        format!(
+                            "etymology of `{}` is circular: {}",
+                            entry.headword,
+                            cycle.join(" -> ")
+                        ),
+                    ));
+                }
+                State::Done => (),
+            }
+        }
+
+        stack.pop();
+        state[i] = State::Done;
+    }
+}
+
+/// State of entry in depth-first search
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Unvisited,
+    Visiting,
+    Done,
+}
+
+/// Quoted DOT string
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(file: &str, diagnostics: &mut Diagnostics) -> Dictionary {
+        Dictionary::parse(file, "dict.ldct", "en", &[], diagnostics)
+    }
+
+    #[test]
+    fn check_etymology_works() {
+        let mut diagnostics = Diagnostics::default();
+        parse(
+            "kata\n  root: mila\n  - cat\nmila\n  from: pita\n  - love\npita\n  compound: kata, nomi\n  - bread\n",
+            &mut diagnostics,
+        );
+
+        let messages = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "error: dict.ldct:7: word `nomi` in `compound` of `pita` is not in dictionary",
+                "error: dict.ldct:1: etymology of `kata` is circular: kata -> mila -> pita -> kata",
+            ]
+        );
+    }
+
+    #[test]
+    fn sources_can_be_ids() {
+        let mut diagnostics = Diagnostics::default();
+        let dict = parse(
+            "kata\n  - cat\nkata\n  - to cut\nkatilo\n  root: kata-2\n  - knife\nkatido\n  from: kata\n  - kitten\n",
+            &mut diagnostics,
+        );
+
+        let messages = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec!["warning: dict.ldct:8: word `kata` in `from` of `katido` is headword of several entries, use id of entry: `kata`, `kata-2`"]
+        );
+
+        let derived = |i: usize| {
+            dict.derived(&dict.entries[i])
+                .iter()
+                .map(|entry| entry.headword.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(derived(0), vec!["katido"]);
+        assert_eq!(derived(1), vec!["katilo"]);
+    }
+
+    #[test]
+    fn derived_works() {
+        let mut diagnostics = Diagnostics::default();
+        let dict = parse(
+            "kata\n  - cat\nkatisto\n  root: kata\n  borrowed: Esperanto \"kato\"\n  - cat keeper\nkatido\n  from: Kata\n  - kitten\n",
+            &mut diagnostics,
+        );
+        assert!(diagnostics.report().is_ok());
+
+        assert_eq!(
+            dict.derived(&dict.entries[0])
+                .iter()
+                .map(|entry| entry.headword.as_str())
+                .collect::<Vec<_>>(),
+            vec!["katisto", "katido"]
+        );
+        assert!(dict.derived(&dict.entries[1]).is_empty());
+
+        assert_eq!(
+            dict.etymology_dot(),
+            "digraph etymology {\n  \
+              \"kata\" [label=\"kata\"];\n  \
+              \"katisto\" [label=\"katisto\"];\n  \
+              \"borrowed:Esperanto \\\"kato\\\"\" [label=\"Esperanto \\\"kato\\\"\", shape=box];\n  \
+              \"katido\" [label=\"katido\"];\n  \
+              \"kata\" -> \"katisto\" [label=root];\n  \
+              \"borrowed:Esperanto \\\"kato\\\"\" -> \"katisto\" [label=borrowed];\n  \
+              \"kata\" -> \"katido\" [label=from];\n\
+            }\n"
+        );
+    }
+}
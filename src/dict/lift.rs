@@ -0,0 +1,342 @@
+use std::{collections::HashMap, error::Error};
+
+use html_escape::{encode_double_quoted_attribute as escape_attr, encode_text as escape_xml};
+use roxmltree::Node;
+
+use super::{Dictionary, Entry, Etymology, Example, Gloss, Sense};
+use crate::{Config, MyError};
+
+/// Write dictionary as LIFT (Lexicon Interchange Format) XML
+///
+/// Headwords and examples are in language of `package.code`, and IPA is in `<code>-fonipa`
+pub(super) fn write_lift(dict: &Dictionary, config: &Config) -> String {
+    let code = &config.package.code;
+    let gloss_lang = config.gloss_language();
+
+    let mut lines = vec![
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+        r#"<lift version="0.13" producer="lingdocs">"#.to_string(),
+    ];
+
+    for entry in &dict.entries {
+        lines.push(format!(r#"  <entry id="{}">"#, escape_attr(&entry.id)));
+        lines.push(format!(
+            "    <lexical-unit>{}</lexical-unit>",
+            form(code, &entry.headword)
+        ));
+        if let Some(slug) = &entry.slug {
+            lines.push(format!(
+                r#"    <trait name="slug" value="{}"/>"#,
+                escape_attr(slug)
+            ));
+        }
+        if let Some(ipa) = &entry.ipa {
+            lines.push(format!(
+                "    <pronunciation>{}</pronunciation>",
+                form(&format!("{}-fonipa", code), ipa)
+            ));
+        }
+
+        for (i, sense) in entry.senses.iter().enumerate() {
+            lines.push(format!(
+                r#"    <sense id="{}_{}" order="{}">"#,
+                escape_attr(&entry.id),
+                i + 1,
+                i + 1
+            ));
+            if let Some(pos) = &entry.pos {
+                lines.push(format!(
+                    r#"      <grammatical-info value="{}"/>"#,
+                    escape_attr(pos)
+                ));
+            }
+            for gloss in &sense.glosses {
+                lines.push(format!(
+                    r#"      <gloss lang="{}"><text>{}</text></gloss>"#,
+                    escape_attr(&gloss.lang),
+                    escape_xml(&gloss.text)
+                ));
+            }
+            for example in &sense.examples {
+                let translation = example
+                    .translation
+                    .as_ref()
+                    .map(|translation| {
+                        format!(
+                            "<translation>{}</translation>",
+                            form(gloss_lang, translation)
+                        )
+                    })
+                    .unwrap_or_default();
+                lines.push(format!(
+                    "      <example>{}{}</example>",
+                    form(code, &example.text),
+                    translation
+                ));
+            }
+            for key in &sense.keys {
+                lines.push(format!(
+                    r#"      <reversal type="{}">{}</reversal>"#,
+                    escape_attr(&key.lang),
+                    form(&key.lang, &key.text)
+                ));
+            }
+            for note in &sense.notes {
+                lines.push(format!("      <note>{}</note>", form(gloss_lang, note)));
+            }
+            lines.push("    </sense>".to_string());
+        }
+
+        for note in &entry.notes {
+            lines.push(format!("    <note>{}</note>", form(gloss_lang, note)));
+        }
+        let see = entry
+            .see
+            .iter()
+            .filter_map(|word| Some(("see", dict.find(word)?)));
+        let sources = entry
+            .etymology
+            .sources()
+            .filter_map(|(field, word)| Some((field, dict.source(word)?)));
+        for (kind, other) in see.chain(sources) {
+            lines.push(format!(
+                r#"    <relation type="{}" ref="{}"/>"#,
+                kind,
+                escape_attr(&other.id)
+            ));
+        }
+        if let Some(borrowed) = &entry.etymology.borrowed {
+            lines.push(format!(
+                r#"    <etymology type="borrowed" source="{}"/>"#,
+                escape_attr(borrowed)
+            ));
+        }
+        lines.push("  </entry>".to_string());
+    }
+
+    lines.push("</lift>".to_string());
+    lines.join("\n") + "\n"
+}
+
+/// Text in language, as `<form>` element
+fn form(lang: &str, text: &str) -> String {
+    format!(
+        r#"<form lang="{}"><text>{}</text></form>"#,
+        escape_attr(lang),
+        escape_xml(text)
+    )
+}
+
+/// Read dictionary from LIFT XML
+///
+/// Part of speech is from first sense with grammatical info. Related words and etymology are from
+/// `see`, `root`, `from` and `compound` relations, and `borrowed` etymology
+pub(super) fn read_lift(file: &str, config: &Config) -> Result<Dictionary, Box<dyn Error>> {
+    let document = roxmltree::Document::parse(file)
+        .map_err(|err| MyError(format!("Could not parse LIFT file: {}", err)))?;
+    let gloss_lang = config.gloss_language();
+
+    let root = document.root_element();
+    if !root.has_tag_name("lift") {
+        return Err(Box::new(MyError(
+            "Expected `<lift>` root element".to_string(),
+        )));
+    }
+
+    let mut entries = Vec::new();
+    // Headword of each entry id, and type and id of relations of each entry
+    let mut headwords = HashMap::<&str, String>::new();
+    let mut relations = Vec::<Vec<(&str, &str)>>::new();
+    for node in children(root, "entry") {
+        let line = document.text_pos_at(node.range().start).row as usize;
+        let headword = child(node, "lexical-unit")
+            .and_then(|unit| form_text(unit, Some(&config.package.code)))
+            .ok_or_else(|| MyError(format!("Line {}: Entry has no lexical unit", line)))?;
+
+        let mut entry = Entry {
+            headword,
+            slug: children(node, "trait")
+                .find(|node| node.attribute("name") == Some("slug"))
+                .and_then(|node| node.attribute("value"))
+                .map(String::from),
+            ipa: child(node, "pronunciation").and_then(|node| form_text(node, None)),
+            etymology: Etymology {
+                borrowed: children(node, "etymology")
+                    .find(|etymology| etymology.attribute("type") == Some("borrowed"))
+                    .and_then(|etymology| etymology.attribute("source"))
+                    .map(String::from),
+                ..Default::default()
+            },
+            notes: children(node, "note")
+                .filter_map(|node| form_text(node, None))
+                .collect(),
+            line,
+            ..Default::default()
+        };
+
+        for node in children(node, "sense") {
+            if entry.pos.is_none() {
+                entry.pos = child(node, "grammatical-info")
+                    .and_then(|info| info.attribute("value"))
+                    .map(String::from);
+            }
+            entry.senses.push(read_sense(node, gloss_lang, line));
+        }
+
+        if let Some(id) = node.attribute("id") {
+            headwords.insert(id, entry.headword.clone());
+        }
+        relations.push(
+            children(node, "relation")
+                .filter_map(|relation| {
+                    Some((relation.attribute("type")?, relation.attribute("ref")?))
+                })
+                .collect(),
+        );
+        entries.push(entry);
+    }
+
+    let mut counts = HashMap::<&str, usize>::new();
+    for word in headwords.values() {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    for (entry, relations) in entries.iter_mut().zip(relations) {
+        for (kind, id) in relations {
+            let Some(word) = headwords.get(id).cloned() else {
+                continue;
+            };
+            // Etymology refers to homographs by id
+            let source = if counts[word.as_str()] > 1 {
+                id.to_string()
+            } else {
+                word.clone()
+            };
+            let etymology = &mut entry.etymology;
+            match kind {
+                "see" => entry.see.push(word),
+                "root" => etymology.root = Some(source),
+                "from" => etymology.from = Some(source),
+                "compound" => etymology.compound.push(source),
+                _ => (),
+            }
+        }
+    }
+
+    Ok(Dictionary::new(entries))
+}
+
+/// Read `<sense>` element, with gloss in `gloss_lang` first
+fn read_sense(node: Node, gloss_lang: &str, line: usize) -> Sense {
+    let mut glosses = vec![Gloss {
+        lang: gloss_lang.to_string(),
+        text: String::new(),
+    }];
+    for gloss in children(node, "gloss") {
+        let lang = gloss.attribute("lang").unwrap_or(gloss_lang);
+        let text = child(gloss, "text").map(element_text).unwrap_or_default();
+        match glosses.iter_mut().find(|other| other.lang == lang) {
+            Some(other) if other.text.is_empty() => other.text = text,
+            // Many glosses in same language
+            Some(other) => other.text = format!("{}; {}", other.text, text),
+            None => glosses.push(Gloss {
+                lang: lang.to_string(),
+                text,
+            }),
+        }
+    }
+
+    Sense {
+        glosses,
+        examples: children(node, "example")
+            .map(|example| Example {
+                text: form_text(example, None).unwrap_or_default(),
+                translation: child(example, "translation").and_then(|node| form_text(node, None)),
+            })
+            .collect(),
+        notes: children(node, "note")
+            .filter_map(|node| form_text(node, None))
+            .collect(),
+        keys: children(node, "reversal")
+            .filter_map(|reversal| {
+                let lang = reversal.attribute("type").unwrap_or(gloss_lang);
+                Some(Gloss {
+                    lang: lang.to_string(),
+                    text: form_text(reversal, None)?,
+                })
+            })
+            .collect(),
+        line,
+    }
+}
+
+/// Child elements with tag name
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.has_tag_name(name))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &'static str) -> Option<Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+/// Text of `<form>` child in language, or first `<form>` child
+fn form_text(node: Node, lang: Option<&str>) -> Option<String> {
+    let form = lang
+        .and_then(|lang| children(node, "form").find(|form| form.attribute("lang") == Some(lang)))
+        .or_else(|| child(node, "form"))?;
+    child(form, "text").map(element_text)
+}
+
+/// Text of element, including text inside `<span>` elements
+fn element_text(node: Node) -> String {
+    node.descendants()
+        .filter(|node| node.is_text())
+        .filter_map(|node| node.text())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Diagnostics;
+
+    #[test]
+    fn lift_round_trip() {
+        let config = Config::from(
+            "[package]\nname = \"abc\"\ncode = \"ab\"\ntranslations = [[\"en\", \"english\"], [\"eo\", \"esperanto\"]]",
+        )
+        .unwrap();
+        let file = "kata\n  ipa: /ˈka.ta/\n  pos: noun\n  note: Informal <&>\n  - cat, feline\n    eo: kato\n    key: cat\n    key.eo: kato\n    ex: kata mi | my cat\n    note: \"Cats\"\n  - to purr\n\nkata\n  see: kata\n  root: kata\n  borrowed: English \"kite\"\n  - kite\n\nkata\n  id: Kata Hat\n  - hat\n";
+
+        let mut diagnostics = Diagnostics::default();
+        let dict = Dictionary::parse(
+            file,
+            "dict.ldct",
+            "en",
+            &config.translation_codes(),
+            &mut diagnostics,
+        );
+        assert!(diagnostics.report().is_ok());
+
+        let lift = write_lift(&dict, &config);
+        assert!(lift
+            .contains(r#"<lexical-unit><form lang="ab"><text>kata</text></form></lexical-unit>"#));
+        assert!(lift.contains(r#"<form lang="ab-fonipa"><text>ˈka.ta</text></form>"#));
+        assert!(lift.contains(r#"<grammatical-info value="noun"/>"#));
+        assert!(lift.contains("Informal &lt;&amp;&gt;"));
+
+        let imported = read_lift(&lift, &config).unwrap();
+        assert_eq!(imported.to_ldct("en"), file);
+        assert_eq!(
+            imported
+                .entries
+                .iter()
+                .map(|entry| entry.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["kata", "kata-2", "kata-hat"]
+        );
+    }
+}
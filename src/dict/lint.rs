@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use html_escape::encode_text as escape_html;
+
+use super::{Dictionary, Entry};
+use crate::{
+    config::LintOptions,
+    convert::{Document, Format, Heading},
+    diagnostic::{Diagnostic, Diagnostics},
+    ipa,
+};
+
+/// Two entries with pronunciations which differ in only one phoneme
+pub struct MinimalPair<'a> {
+    /// Phonemes which differ, in order of entries
+    pub contrast: (String, String),
+    pub first: &'a Entry,
+    pub second: &'a Entry,
+}
+
+impl Dictionary {
+    /// Warn about duplicate entries, homographs, homophones and minimal pairs, as set in options
+    ///
+    /// Each warning is on the later entry
+    pub fn lint(
+        &self,
+        options: &LintOptions,
+        gloss_lang: &str,
+        filepath: &str,
+        diagnostics: &mut Diagnostics,
+    ) {
+        // Earlier entries with each headword, and with each pronunciation
+        let mut spellings = HashMap::<&str, Vec<&Entry>>::new();
+        let mut pronunciations = HashMap::<Vec<String>, Vec<&Entry>>::new();
+
+        for entry in &self.entries {
+            let mut warn = |message: String| {
+                diagnostics.push(Diagnostic::warning(filepath, Some(entry.line), message))
+            };
+
+            let spelling = spellings.entry(&entry.headword).or_default();
+            for earlier in spelling.iter() {
+                let is_duplicate = entry.ipa == earlier.ipa
+                    && entry.gloss(gloss_lang) == earlier.gloss(gloss_lang);
+                if is_duplicate && options.duplicates {
+                    warn(format!(
+                        "entry `{}` is a duplicate of entry on line {}",
+                        entry.headword, earlier.line
+                    ));
+                } else if !is_duplicate && options.homographs {
+                    warn(format!(
+                        "entry `{}` has same spelling as entry on line {}",
+                        entry.headword, earlier.line
+                    ));
+                }
+            }
+            spelling.push(entry);
+
+            if entry.ipa.is_some() {
+                let pronunciation = pronunciations.entry(phonemes(entry)).or_default();
+                for earlier in pronunciation.iter() {
+                    if options.homophones && earlier.headword != entry.headword {
+                        warn(format!(
+                            "entry `{}` has same pronunciation as `{}` on line {}",
+                            entry.headword, earlier.headword, earlier.line
+                        ));
+                    }
+                }
+                pronunciation.push(entry);
+            }
+        }
+
+        if options.minimal_pairs {
+            for pair in self.minimal_pairs(&options.contrasts()) {
+                diagnostics.push(Diagnostic::warning(
+                    filepath,
+                    Some(pair.second.line),
+                    format!(
+                        "entries `{}` and `{}` are a minimal pair for /{}/ and /{}/",
+                        pair.first.headword, pair.second.headword, pair.contrast.0, pair.contrast.1
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Pairs of entries with IPA which differ in one phoneme, in order of dictionary
+    ///
+    /// Only pairs with one of `contrasts` are given, or all pairs if there are no contrasts
+    pub fn minimal_pairs(&self, contrasts: &[(String, String)]) -> Vec<MinimalPair<'_>> {
+        // Entries with each pronunciation, with one phoneme replaced by its position
+        let mut groups = HashMap::<(Vec<String>, usize), Vec<(usize, String)>>::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            let phonemes = phonemes(entry);
+            for position in 0..phonemes.len() {
+                let mut key = phonemes.clone();
+                let phoneme = std::mem::take(&mut key[position]);
+                groups
+                    .entry((key, position))
+                    .or_default()
+                    .push((i, phoneme));
+            }
+        }
+
+        let mut pairs = Vec::<(usize, usize, String, String)>::new();
+        for group in groups.values() {
+            for (j, (second, b)) in group.iter().enumerate() {
+                for (first, a) in &group[..j] {
+                    let is_contrast = contrasts.is_empty()
+                        || contrasts
+                            .iter()
+                            .any(|(x, y)| (x == a && y == b) || (x == b && y == a));
+                    if a != b && is_contrast {
+                        pairs.push((*first, *second, a.clone(), b.clone()));
+                    }
+                }
+            }
+        }
+        pairs.sort();
+
+        pairs
+            .into_iter()
+            .map(|(first, second, a, b)| MinimalPair {
+                contrast: (a, b),
+                first: &self.entries[first],
+                second: &self.entries[second],
+            })
+            .collect()
+    }
+}
+
+/// Phonemes of IPA of entry, or none
+fn phonemes(entry: &Entry) -> Vec<String> {
+    entry.ipa.as_deref().map(ipa::phonemes).unwrap_or_default()
+}
+
+/// Render minimal pairs as page, with a table for each contrast, in order of `contrasts`
+///
+/// `href` is path of dictionary page, relative to page
+pub fn minimal_pairs_to_html(
+    pairs: &[MinimalPair],
+    contrasts: &[(String, String)],
+    gloss_lang: &str,
+    href: &str,
+) -> Document {
+    // Pairs of each contrast, in order of contrasts, then order of first pair
+    let mut groups = Vec::<((&str, &str), Vec<(&Entry, &Entry)>)>::new();
+    for pair in pairs {
+        let (a, b) = (pair.contrast.0.as_str(), pair.contrast.1.as_str());
+        // Phonemes in order of contrast, or in Unicode order
+        let is_reversed = match contrasts
+            .iter()
+            .find(|(x, y)| (x == a && y == b) || (x == b && y == a))
+        {
+            Some((x, _)) => x == b,
+            None => a > b,
+        };
+        let (contrast, entries) = if is_reversed {
+            ((b, a), (pair.second, pair.first))
+        } else {
+            ((a, b), (pair.first, pair.second))
+        };
+
+        match groups.iter_mut().find(|(other, _)| *other == contrast) {
+            Some((_, group)) => group.push(entries),
+            None => groups.push((contrast, vec![entries])),
+        }
+    }
+    groups.sort_by_key(|((a, b), _)| {
+        contrasts
+            .iter()
+            .position(|(x, y)| x == a && y == b)
+            .unwrap_or(contrasts.len())
+    });
+
+    let mut body = Vec::new();
+    let mut headings = Vec::new();
+    for ((a, b), group) in &groups {
+        let heading = Heading {
+            level: 1,
+            text: format!("/{}/ and /{}/", a, b),
+            id: super::slug(&format!("pairs {} {}", a, b)),
+        };
+        body.push(format!(
+            r#"<h2 class="header" id="{}"> {} </h2>"#,
+            heading.id,
+            escape_html(&heading.text)
+        ));
+        headings.push(heading);
+
+        body.push("<table class=\"minimal-pairs\">".to_string());
+        for (first, second) in group {
+            body.push(format!(
+                "<tr> {} {} </tr>",
+                pair_cell(first, gloss_lang, href),
+                pair_cell(second, gloss_lang, href)
+            ));
+        }
+        body.push("</table>".to_string());
+    }
+
+    Document {
+        body: body.join("\n"),
+        headings,
+        mentions: Vec::new(),
+    }
+}
+
+/// Table cell of entry in minimal pair, linked to dictionary, with IPA and gloss
+fn pair_cell(entry: &Entry, gloss_lang: &str, href: &str) -> String {
+    format!(
+        r#"<td> <a class="link" href="{}#{}"> {} </a> {} <span class="gloss"> {} </span> </td>"#,
+        href,
+        entry.id,
+        Format::Text(String::new()).format(&escape_html(&entry.headword)),
+        Format::BroadIPA.format(&escape_html(entry.ipa.as_deref().unwrap_or_default())),
+        escape_html(&entry.gloss(gloss_lang))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(file: &str) -> Dictionary {
+        Dictionary::parse(file, "dict.ldct", "en", &[], &mut Diagnostics::default())
+    }
+
+    #[test]
+    fn lint_works() {
+        let dict = parse(
+            "pata\n  ipa: /ˈpa.ta/\n  - duck\n\
+            bata\n  ipa: /ˈba.ta/\n  - boat\n\
+            pata\n  ipa: /ˈpa.ta/\n  - duck\n\
+            pata\n  - step\n\
+            phata\n  ipa: /pa.ta/\n  - ground\n",
+        );
+        let options = LintOptions {
+            homographs: true,
+            minimal_pairs: true,
+            contrasts: vec!["p b".to_string()],
+            ..Default::default()
+        };
+
+        let mut diagnostics = Diagnostics::default();
+        dict.lint(&options, "en", "dict.ldct", &mut diagnostics);
+        let messages = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "warning: dict.ldct:7: entry `pata` is a duplicate of entry on line 1",
+                "warning: dict.ldct:10: entry `pata` has same spelling as entry on line 1",
+                "warning: dict.ldct:10: entry `pata` has same spelling as entry on line 7",
+                "warning: dict.ldct:12: entry `phata` has same pronunciation as `pata` on line 1",
+                "warning: dict.ldct:12: entry `phata` has same pronunciation as `pata` on line 7",
+                "warning: dict.ldct:4: entries `pata` and `bata` are a minimal pair for /p/ and /b/",
+                "warning: dict.ldct:7: entries `bata` and `pata` are a minimal pair for /b/ and /p/",
+                "warning: dict.ldct:12: entries `bata` and `phata` are a minimal pair for /b/ and /p/",
+            ]
+        );
+    }
+
+    #[test]
+    fn minimal_pairs_works() {
+        let dict = parse(
+            "kata\n  ipa: /ka.ta/\n  - cat\n\
+            gata\n  ipa: /ga.ta/\n  - goose\n\
+            kasa\n  ipa: /ka.sa/\n  - house\n\
+            kat\n  ipa: /kat/\n  - short\n",
+        );
+
+        let pairs = dict.minimal_pairs(&[]);
+        assert_eq!(
+            pairs
+                .iter()
+                .map(|pair| (
+                    pair.first.headword.as_str(),
+                    pair.second.headword.as_str(),
+                    pair.contrast.0.as_str(),
+                    pair.contrast.1.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![("kata", "gata", "k", "g"), ("kata", "kasa", "t", "s")]
+        );
+
+        let contrasts = [("g".to_string(), "k".to_string())];
+        let document = minimal_pairs_to_html(
+            &dict.minimal_pairs(&contrasts),
+            &contrasts,
+            "en",
+            "dict.html",
+        );
+        assert_eq!(document.headings[0].text, "/g/ and /k/");
+        assert!(document.body.find("gata").unwrap() < document.body.find("kata").unwrap());
+    }
+}
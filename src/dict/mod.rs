@@ -0,0 +1,770 @@
+mod coverage;
+mod etymology;
+mod lift;
+mod lint;
+mod page;
+mod reverse;
+mod search;
+mod stats;
+mod table;
+
+pub use coverage::{coverage_to_html, ConceptList};
+pub use etymology::Etymology;
+pub use lint::minimal_pairs_to_html;
+pub use page::{dict_to_html, entry_page_to_html};
+pub use reverse::reverse_to_html;
+pub use search::{search_index, search_index_script, SEARCH_BOX, SEARCH_SCRIPT};
+pub use stats::Stats;
+
+use std::{collections::HashMap, error::Error, fmt, fs, path::Path};
+
+use clap::ValueEnum;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    Config,
+};
+
+/// Dictionary, parsed from `.ldct` file
+///
+/// ```txt
+/// # Comment
+/// kata
+///   ipa: ˈka.ta
+///   pos: noun
+///   note: Informal
+///   - cat; small feline
+///     eo: kato
+///     ex: kata mi | my cat
+///     key: cat, feline
+///   - to purr
+/// ```
+///
+/// Entries start with an unindented headword. Indented lines are fields of the entry, and `- gloss`
+/// starts a sense. Lines indented further than `-` are fields of that sense
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    pub entries: Vec<Entry>,
+    /// Indexes of entries which derive from entry, by id of entry
+    derived: HashMap<String, Vec<usize>>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Entry {
+    pub headword: String,
+    /// Unique id, for links to entry and entry page
+    pub id: String,
+    /// Id from `id` field, instead of headword, for permalinks which do not change with headword
+    pub slug: Option<String>,
+    /// Broad transcription, without slashes
+    pub ipa: Option<String>,
+    /// Part of speech
+    pub pos: Option<String>,
+    /// Headwords of related entries, from `see` field
+    pub see: Vec<String>,
+    pub etymology: Etymology,
+    pub senses: Vec<Sense>,
+    pub notes: Vec<String>,
+    /// Line of headword in file
+    pub line: usize,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Sense {
+    /// Glosses in each language, gloss language first
+    pub glosses: Vec<Gloss>,
+    pub examples: Vec<Example>,
+    pub notes: Vec<String>,
+    /// Keys for reverse dictionary, from `key` and `key.lang` fields, instead of keywords of gloss
+    pub keys: Vec<Gloss>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gloss {
+    /// Language code, from `package.translations`
+    pub lang: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Example {
+    pub text: String,
+    pub translation: Option<String>,
+}
+
+/// Format of exported dictionary
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum DictFormat {
+    Csv,
+    Tsv,
+    Json,
+    /// Lexicon Interchange Format (XML)
+    Lift,
+}
+
+impl DictFormat {
+    /// Format from file extension
+    pub fn from_path(path: &str) -> Option<Self> {
+        match path.rsplit('.').next()?.to_lowercase().as_str() {
+            "csv" => Some(DictFormat::Csv),
+            "tsv" => Some(DictFormat::Tsv),
+            "json" => Some(DictFormat::Json),
+            "lift" | "xml" => Some(DictFormat::Lift),
+            _ => None,
+        }
+    }
+}
+
+/// Write dictionary in other format
+pub fn export(
+    dict: &Dictionary,
+    format: DictFormat,
+    config: &Config,
+) -> Result<String, Box<dyn Error>> {
+    match format {
+        DictFormat::Lift => Ok(lift::write_lift(dict, config)),
+        _ => table::write_table(dict, format, config),
+    }
+}
+
+/// Read dictionary from other format
+pub fn import(
+    file: &str,
+    format: DictFormat,
+    config: &Config,
+) -> Result<Dictionary, Box<dyn Error>> {
+    match format {
+        DictFormat::Lift => lift::read_lift(file, config),
+        _ => table::read_table(file, format, config),
+    }
+}
+
+impl Sense {
+    /// Gloss in language, if any
+    pub fn gloss(&self, lang: &str) -> Option<&str> {
+        self.glosses
+            .iter()
+            .find(|gloss| gloss.lang == lang)
+            .map(|gloss| gloss.text.as_str())
+    }
+}
+
+impl Entry {
+    /// Glosses of all senses in language, separated with `;`
+    pub fn gloss(&self, lang: &str) -> String {
+        self.senses
+            .iter()
+            .filter_map(|sense| sense.gloss(lang))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// Dictionary entries for `{% word}` statements in a page
+pub struct Lookup<'a> {
+    pub dictionary: &'a Dictionary,
+    pub gloss_lang: &'a str,
+    /// Path of dictionary page, relative to page
+    pub href: String,
+}
+
+impl Dictionary {
+    /// Dictionary of entries, with unique ids
+    fn new(entries: Vec<Entry>) -> Self {
+        let mut dict = Dictionary {
+            entries,
+            derived: HashMap::new(),
+        };
+        dict.assign_ids();
+        dict.derived = dict.index_derived();
+        dict
+    }
+
+    /// Parse dictionary file of project, if it exists
+    pub fn load(config: &Config, diagnostics: &mut Diagnostics) -> Option<Self> {
+        let path = Path::new(&config.files.source).join(&config.files.dict);
+        if !path.exists() {
+            return None;
+        }
+
+        let file = fs::read_to_string(path).expect("Could not read dictionary file");
+        Some(Dictionary::parse(
+            &file,
+            &config.files.dict,
+            config.gloss_language(),
+            &config.translation_codes(),
+            diagnostics,
+        ))
+    }
+
+    /// First entry with headword, ignoring case if no headword matches exactly
+    pub fn find(&self, word: &str) -> Option<&Entry> {
+        self.position(word).map(|i| &self.entries[i])
+    }
+
+    /// Index of first entry with headword, ignoring case if no headword matches exactly
+    fn position(&self, word: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.headword == word)
+            .or_else(|| {
+                let word = word.to_lowercase();
+                self.entries
+                    .iter()
+                    .position(|entry| entry.headword.to_lowercase() == word)
+            })
+    }
+
+    /// Parse `.ldct` file
+    ///
+    /// `gloss_lang` is language of `- gloss` lines, and `langs` are codes of other languages, which can
+    /// be used as sense fields
+    pub fn parse(
+        file: &str,
+        filepath: &str,
+        gloss_lang: &str,
+        langs: &[String],
+        diagnostics: &mut Diagnostics,
+    ) -> Self {
+        let mut entries = Vec::<Entry>::new();
+        // Indent of `-` of current sense
+        let mut sense_indent = None;
+
+        for (line_num, line) in file.lines().enumerate() {
+            let line_num = line_num + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let indent = line.len() - line.trim_start().len();
+
+            // Headword
+            if indent == 0 {
+                entries.push(Entry {
+                    headword: trimmed.to_string(),
+                    line: line_num,
+                    ..Default::default()
+                });
+                sense_indent = None;
+                continue;
+            }
+
+            let error = |message: String| Diagnostic::error(filepath, Some(line_num), message);
+
+            let entry = match entries.last_mut() {
+                Some(entry) => entry,
+                None => {
+                    diagnostics.push(error("expected headword before indented line".to_string()));
+                    continue;
+                }
+            };
+
+            // Sense
+            if let Some(gloss) = trimmed.strip_prefix('-') {
+                entry.senses.push(Sense {
+                    glosses: vec![Gloss {
+                        lang: gloss_lang.to_string(),
+                        text: gloss.trim().to_string(),
+                    }],
+                    line: line_num,
+                    ..Default::default()
+                });
+                sense_indent = Some(indent);
+                continue;
+            }
+
+            let (key, value) = match trimmed.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim().to_string()),
+                None => {
+                    diagnostics.push(error(format!(
+                        "expected `field: value` or `- gloss`, found `{}`",
+                        trimmed
+                    )));
+                    continue;
+                }
+            };
+
+            // Sense field
+            if let Some(sense) = entry
+                .senses
+                .last_mut()
+                .filter(|_| sense_indent.is_some_and(|sense_indent| indent > sense_indent))
+            {
+                match key {
+                    "ex" => sense.examples.push(parse_example(&value)),
+                    "note" => sense.notes.push(value),
+                    "key" => sense.keys.extend(parse_keys(gloss_lang, &value)),
+                    key if key
+                        .strip_prefix("key.")
+                        .is_some_and(|lang| langs.iter().any(|other| other == lang)) =>
+                    {
+                        sense.keys.extend(parse_keys(&key["key.".len()..], &value))
+                    }
+                    lang if langs.iter().any(|other| other == lang) => sense.glosses.push(Gloss {
+                        lang: lang.to_string(),
+                        text: value,
+                    }),
+                    _ => diagnostics.push(error(format!("unknown sense field `{}`", key))),
+                }
+                continue;
+            }
+            sense_indent = None;
+
+            // Entry field
+            let field = match key {
+                "ipa" => &mut entry.ipa,
+                "pos" => &mut entry.pos,
+                "id" => &mut entry.slug,
+                "root" => &mut entry.etymology.root,
+                "from" => &mut entry.etymology.from,
+                "borrowed" => &mut entry.etymology.borrowed,
+                "note" => {
+                    entry.notes.push(value);
+                    continue;
+                }
+                "see" => {
+                    entry.see.extend(split_list(&value));
+                    continue;
+                }
+                "compound" => {
+                    entry.etymology.compound.extend(split_list(&value));
+                    continue;
+                }
+                _ => {
+                    diagnostics.push(error(format!("unknown field `{}`", key)));
+                    continue;
+                }
+            };
+            if field.is_some() {
+                diagnostics.push(error(format!("field `{}` is given twice", key)));
+            }
+            *field = Some(value.trim_matches('/').trim().to_string());
+        }
+
+        for entry in &entries {
+            if entry.senses.is_empty() {
+                diagnostics.push(Diagnostic::error(
+                    filepath,
+                    Some(entry.line),
+                    format!("entry `{}` has no senses", entry.headword),
+                ));
+            }
+        }
+
+        let dict = Dictionary::new(entries);
+        for entry in &dict.entries {
+            for word in &entry.see {
+                if dict.find(word).is_none() {
+                    diagnostics.push(Diagnostic::error(
+                        filepath,
+                        Some(entry.line),
+                        format!(
+                            "word `{}` in `see` of `{}` is not in dictionary",
+                            word, entry.headword
+                        ),
+                    ));
+                }
+            }
+        }
+        dict.check_etymology(filepath, diagnostics);
+        dict
+    }
+
+    /// Entries with same headword, and entries in `see` field of entry
+    pub fn related(&self, entry: &Entry) -> Vec<&Entry> {
+        let mut related = Vec::<&Entry>::new();
+        let homographs = self
+            .entries
+            .iter()
+            .filter(|other| other.headword == entry.headword);
+        let see = entry.see.iter().filter_map(|word| self.find(word));
+
+        for other in homographs.chain(see) {
+            if other.id != entry.id && !related.iter().any(|related| related.id == other.id) {
+                related.push(other);
+            }
+        }
+        related
+    }
+
+    /// Write dictionary as `.ldct` file
+    pub fn to_ldct(&self, gloss_lang: &str) -> String {
+        self.entries
+            .iter()
+            .map(|entry| entry.to_ldct(gloss_lang))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Entries which are added, changed, or removed in other dictionary, by id
+    pub fn diff(&self, other: &Dictionary, gloss_lang: &str) -> Diff {
+        let mut diff = Diff::default();
+
+        for entry in &other.entries {
+            match self.entries.iter().find(|old| old.id == entry.id) {
+                None => diff.added.push(entry.id.clone()),
+                Some(old) if old.to_ldct(gloss_lang) != entry.to_ldct(gloss_lang) => {
+                    diff.changed.push(entry.id.clone())
+                }
+                Some(_) => (),
+            }
+        }
+        for entry in &self.entries {
+            if !other.entries.iter().any(|new| new.id == entry.id) {
+                diff.removed.push(entry.id.clone());
+            }
+        }
+
+        diff
+    }
+
+    /// Give each entry a unique id from its headword
+    fn assign_ids(&mut self) {
+        let mut counts = HashMap::<String, usize>::new();
+
+        for entry in &mut self.entries {
+            let id = slug(entry.slug.as_deref().unwrap_or(&entry.headword));
+            let count = counts.entry(id.clone()).or_insert(0);
+            *count += 1;
+
+            entry.id = if *count > 1 {
+                format!("{}-{}", id, count)
+            } else {
+                id
+            };
+        }
+    }
+}
+
+impl Entry {
+    /// Write entry as `.ldct` lines, ending with linebreak
+    fn to_ldct(&self, gloss_lang: &str) -> String {
+        let mut lines = vec![self.headword.clone()];
+        if let Some(slug) = &self.slug {
+            lines.push(format!("  id: {}", slug));
+        }
+        if let Some(ipa) = &self.ipa {
+            lines.push(format!("  ipa: /{}/", ipa));
+        }
+        if let Some(pos) = &self.pos {
+            lines.push(format!("  pos: {}", pos));
+        }
+        if !self.see.is_empty() {
+            lines.push(format!("  see: {}", self.see.join(", ")));
+        }
+        let etymology = &self.etymology;
+        if let Some(root) = &etymology.root {
+            lines.push(format!("  root: {}", root));
+        }
+        if let Some(from) = &etymology.from {
+            lines.push(format!("  from: {}", from));
+        }
+        if !etymology.compound.is_empty() {
+            lines.push(format!("  compound: {}", etymology.compound.join(", ")));
+        }
+        if let Some(borrowed) = &etymology.borrowed {
+            lines.push(format!("  borrowed: {}", borrowed));
+        }
+        for note in &self.notes {
+            lines.push(format!("  note: {}", note));
+        }
+
+        for sense in &self.senses {
+            lines.push(
+                format!("  - {}", sense.gloss(gloss_lang).unwrap_or_default())
+                    .trim_end()
+                    .to_string(),
+            );
+            for gloss in sense
+                .glosses
+                .iter()
+                .filter(|gloss| gloss.lang != gloss_lang)
+            {
+                lines.push(format!("    {}: {}", gloss.lang, gloss.text));
+            }
+            for (lang, keys) in group_keys(&sense.keys) {
+                if lang == gloss_lang {
+                    lines.push(format!("    key: {}", keys));
+                } else {
+                    lines.push(format!("    key.{}: {}", lang, keys));
+                }
+            }
+            for example in &sense.examples {
+                lines.push(format!("    ex: {}", example));
+            }
+            for note in &sense.notes {
+                lines.push(format!("    note: {}", note));
+            }
+        }
+
+        lines.join("\n") + "\n"
+    }
+}
+
+impl fmt::Display for Example {
+    /// Example as `text | translation`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.translation {
+            Some(translation) => write!(f, "{} | {}", self.text, translation),
+            None => write!(f, "{}", self.text),
+        }
+    }
+}
+
+/// Changed entries between two versions of dictionary, as ids
+#[derive(Debug, Default)]
+pub struct Diff {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (label, ids) in [
+            ("Added", &self.added),
+            ("Changed", &self.changed),
+            ("Removed", &self.removed),
+        ] {
+            if !ids.is_empty() {
+                writeln!(f, "{}: {}", label, ids.join(", "))?;
+            }
+        }
+        write!(
+            f,
+            "{} added, {} changed, {} removed",
+            self.added.len(),
+            self.changed.len(),
+            self.removed.len()
+        )
+    }
+}
+
+/// Keys of each language, comma-separated, in order of first key of language
+fn group_keys(keys: &[Gloss]) -> Vec<(&str, String)> {
+    let mut groups = Vec::<(&str, Vec<&str>)>::new();
+    for key in keys {
+        match groups.iter_mut().find(|(lang, _)| *lang == key.lang) {
+            Some((_, texts)) => texts.push(&key.text),
+            None => groups.push((&key.lang, vec![&key.text])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(lang, texts)| (lang, texts.join(", ")))
+        .collect()
+}
+
+/// Parse example, as `text | translation`
+fn parse_example(value: &str) -> Example {
+    match value.split_once('|') {
+        Some((text, translation)) => Example {
+            text: text.trim().to_string(),
+            translation: Some(translation.trim().to_string()),
+        },
+        None => Example {
+            text: value.trim().to_string(),
+            translation: None,
+        },
+    }
+}
+
+/// Split comma-separated list, without empty items
+fn split_list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+}
+
+/// Parse comma-separated keys in language
+fn parse_keys(lang: &str, value: &str) -> Vec<Gloss> {
+    split_list(value)
+        .map(|key| Gloss {
+            lang: lang.to_string(),
+            text: key.to_string(),
+        })
+        .collect()
+}
+
+/// Lowercase word, with letters and numbers of any script, and `-` for spaces
+///
+/// Normalized to NFC, so slug is same for composed and decomposed characters
+pub fn slug(word: &str) -> String {
+    let slug = word
+        .trim()
+        .to_lowercase()
+        .nfc()
+        .filter_map(|ch| {
+            if ch.is_whitespace() || ch == '-' {
+                Some('-')
+            } else if ch.is_alphanumeric() {
+                Some(ch)
+            } else {
+                None
+            }
+        })
+        .collect::<String>();
+
+    if slug.is_empty() {
+        "entry".to_string()
+    } else {
+        slug
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(file: &str, diagnostics: &mut Diagnostics) -> Dictionary {
+        Dictionary::parse(
+            file,
+            "dict.ldct",
+            "en",
+            &["en".to_string(), "eo".to_string()],
+            diagnostics,
+        )
+    }
+
+    #[test]
+    fn parse_works() {
+        let mut diagnostics = Diagnostics::default();
+        let dict = parse(
+            "# comment\n\
+            kata\n  \
+              ipa: /ˈka.ta/\n  \
+              pos: noun\n  \
+              - cat; small feline\n    \
+                eo: kato\n    \
+                ex: kata mi | my cat\n    \
+                key.eo: kato, katino\n  \
+              - to purr\n  \
+              note: Informal\n\
+            \n\
+            kata\n  \
+              - kite\n",
+            &mut diagnostics,
+        );
+        assert!(diagnostics.report().is_ok());
+
+        assert_eq!(dict.entries.len(), 2);
+        let entry = &dict.entries[0];
+        assert_eq!(entry.headword, "kata");
+        assert_eq!(entry.id, "kata");
+        assert_eq!(entry.ipa.as_deref(), Some("ˈka.ta"));
+        assert_eq!(entry.pos.as_deref(), Some("noun"));
+        assert_eq!(entry.notes, vec!["Informal"]);
+        assert_eq!(entry.line, 2);
+
+        assert_eq!(entry.senses.len(), 2);
+        assert_eq!(
+            entry.senses[0].glosses,
+            vec![
+                Gloss {
+                    lang: "en".to_string(),
+                    text: "cat; small feline".to_string()
+                },
+                Gloss {
+                    lang: "eo".to_string(),
+                    text: "kato".to_string()
+                }
+            ]
+        );
+        assert_eq!(
+            entry.senses[0].examples,
+            vec![Example {
+                text: "kata mi".to_string(),
+                translation: Some("my cat".to_string())
+            }]
+        );
+
+        assert_eq!(
+            entry.senses[0]
+                .keys
+                .iter()
+                .map(|key| (key.lang.as_str(), key.text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("eo", "kato"), ("eo", "katino")]
+        );
+        assert_eq!(entry.gloss("en"), "cat; small feline; to purr");
+
+        assert_eq!(dict.entries[1].id, "kata-2");
+        assert_eq!(dict.find("Kata").map(|entry| entry.line), Some(2));
+        assert!(dict.find("kat").is_none());
+    }
+
+    #[test]
+    fn parse_reports_errors() {
+        let mut diagnostics = Diagnostics::default();
+        parse(
+            "  - orphan\nkata\n  color: red\n  - cat\n    xx: kato\n  ipa: a\n  ipa: b\npita\n",
+            &mut diagnostics,
+        );
+
+        let messages = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "error: dict.ldct:1: expected headword before indented line",
+                "error: dict.ldct:3: unknown field `color`",
+                "error: dict.ldct:5: unknown sense field `xx`",
+                "error: dict.ldct:7: field `ipa` is given twice",
+                "error: dict.ldct:8: entry `pita` has no senses",
+            ]
+        );
+    }
+
+    #[test]
+    fn related_works() {
+        let mut diagnostics = Diagnostics::default();
+        let dict = parse(
+            "kata
+  id: cat
+  see: mila, pita
+  - cat
+mila
+  - to love
+kata
+  - kite
+",
+            &mut diagnostics,
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["error: dict.ldct:1: word `pita` in `see` of `kata` is not in dictionary"]
+        );
+
+        let entry = &dict.entries[0];
+        assert_eq!(entry.id, "cat");
+        assert_eq!(entry.see, vec!["mila", "pita"]);
+        assert_eq!(
+            dict.related(entry)
+                .iter()
+                .map(|entry| entry.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["kata", "mila"]
+        );
+        assert_eq!(dict.entries[2].id, "kata");
+        assert!(dict
+            .to_ldct("en")
+            .starts_with("kata\n  id: cat\n  see: mila, pita\n"));
+    }
+
+    #[test]
+    fn slug_works() {
+        assert_eq!(slug("Kata"), "kata");
+        assert_eq!(slug("ŝi ĉu?"), "ŝi-ĉu");
+        assert_eq!(slug("s\u{302}alo"), "ŝalo");
+        assert_eq!(slug("!?"), "entry");
+    }
+}
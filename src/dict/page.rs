@@ -0,0 +1,522 @@
+use html_escape::encode_text as escape_html;
+
+use super::{Dictionary, Entry, Lookup, Sense};
+use crate::{
+    convert::{Document, Format, Heading},
+    orthography::Orthography,
+};
+
+/// Render dictionary as page, with entries grouped by first letter, in collation order
+///
+/// `translations` are language codes and names, for glosses not in `gloss_lang`. `entry_dir` is
+/// directory of entry pages, relative to dictionary page, if entries have pages
+pub fn dict_to_html(
+    dict: &Dictionary,
+    orthography: &Orthography,
+    gloss_lang: &str,
+    translations: &[(String, String)],
+    entry_dir: Option<&str>,
+) -> Document {
+    let mut entries = dict.entries.iter().collect::<Vec<_>>();
+    orthography.sort_by_word(&mut entries, |entry| &entry.headword);
+
+    letters_to_html(
+        &entries,
+        orthography,
+        |entry| &entry.headword,
+        |entry| {
+            let permalink = entry_dir.map(|dir| format!("{}{}.html", dir, entry.id));
+            entry_to_html(
+                entry,
+                dict,
+                gloss_lang,
+                translations,
+                |other| format!("#{}", other.id),
+                permalink.as_deref(),
+            )
+        },
+        "dict",
+    )
+}
+
+/// Render page of one entry, with related entries, and pages which link to entry
+///
+/// Related entries are in same directory as page. `mentions` are titles and paths of pages,
+/// relative to entry page. `dict_href` is path of dictionary page, relative to entry page
+pub fn entry_page_to_html(
+    entry: &Entry,
+    dict: &Dictionary,
+    gloss_lang: &str,
+    translations: &[(String, String)],
+    mentions: &[(String, String)],
+    dict_href: &str,
+) -> Document {
+    let mut body = vec![format!(
+        "<dl class=\"dict\">\n{}\n</dl>",
+        entry_to_html(
+            entry,
+            dict,
+            gloss_lang,
+            translations,
+            |other| format!("{}.html", other.id),
+            None
+        )
+    )];
+    let mut headings = Vec::new();
+
+    let related = dict.related(entry);
+    if !related.is_empty() {
+        let items = related
+            .iter()
+            .map(|other| {
+                format!(
+                    r#"<li> <a class="link" href="{}.html"> {} </a> <span class="gloss"> {} </span> </li>"#,
+                    other.id,
+                    Format::Text(String::new()).format(&escape_html(&other.headword)),
+                    escape_html(&other.gloss(gloss_lang))
+                )
+            })
+            .collect::<Vec<_>>();
+        push_section(&mut body, &mut headings, "Related words", "related", items);
+    }
+
+    if !mentions.is_empty() {
+        let items = mentions
+            .iter()
+            .map(|(title, href)| {
+                format!(
+                    r#"<li> <a class="link" href="{}"> {} </a> </li>"#,
+                    href,
+                    escape_html(title)
+                )
+            })
+            .collect::<Vec<_>>();
+        push_section(&mut body, &mut headings, "Mentioned in", "mentions", items);
+    }
+
+    body.push(format!(
+        r#"<p> <a class="link" href="{}#{}"> Dictionary </a> </p>"#,
+        dict_href, entry.id
+    ));
+
+    Document {
+        body: body.join("\n"),
+        headings,
+        mentions: Vec::new(),
+    }
+}
+
+/// Add section with heading and list to entry page
+fn push_section(
+    body: &mut Vec<String>,
+    headings: &mut Vec<Heading>,
+    title: &str,
+    id: &str,
+    items: Vec<String>,
+) {
+    body.push(format!(
+        r#"<h2 class="header" id="{}"> {} </h2>"#,
+        id, title
+    ));
+    body.push(format!(
+        "<ul class=\"{}\">\n{}\n</ul>",
+        id,
+        items.join("\n")
+    ));
+    headings.push(Heading {
+        level: 1,
+        text: title.to_string(),
+        id: id.to_string(),
+    });
+}
+
+/// Render sorted items grouped by first letter, with index of letters
+///
+/// Index has every letter of alphabet, and any other first letters of items
+pub fn letters_to_html<T>(
+    items: &[T],
+    orthography: &Orthography,
+    word: impl Fn(&T) -> &str,
+    render: impl Fn(&T) -> String,
+    class: &str,
+) -> Document {
+    // Items grouped by first letter, in order
+    let mut letters = Vec::<(String, Vec<&T>)>::new();
+    for item in items {
+        let letter = orthography.first_letter(word(item));
+        match letters.last_mut() {
+            Some((last, group)) if *last == letter => group.push(item),
+            _ => letters.push((letter, vec![item])),
+        }
+    }
+
+    let mut body = Vec::<String>::new();
+    let mut headings = Vec::<Heading>::new();
+
+    let mut index = orthography.letter_headings();
+    for (letter, _) in &letters {
+        if !index.contains(letter) {
+            index.push(letter.clone());
+        }
+    }
+    body.push(format!(
+        r#"<nav class="dict-letters"> {} </nav>"#,
+        index
+            .iter()
+            .map(|letter| {
+                if letters.iter().any(|(other, _)| other == letter) {
+                    format!(
+                        r##"<a class="link" href="#{}"> {} </a>"##,
+                        letter_id(letter),
+                        escape_html(letter)
+                    )
+                } else {
+                    format!(
+                        r#"<span class="link empty"> {} </span>"#,
+                        escape_html(letter)
+                    )
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    ));
+
+    for (letter, group) in &letters {
+        let heading = Heading {
+            level: 1,
+            text: letter.clone(),
+            id: letter_id(letter),
+        };
+        body.push(format!(
+            r#"<h2 class="header letter" id="{}"> {} </h2>"#,
+            heading.id,
+            escape_html(letter)
+        ));
+        headings.push(heading);
+
+        body.push(format!(r#"<dl class="{}">"#, class));
+        for item in group {
+            body.push(render(item));
+        }
+        body.push("</dl>".to_string());
+    }
+
+    Document {
+        body: body.join("\n"),
+        headings,
+        mentions: Vec::new(),
+    }
+}
+
+impl Lookup<'_> {
+    /// Entry of html-escaped word
+    pub fn find(&self, word: &str) -> Option<&Entry> {
+        self.dictionary
+            .find(&html_escape::decode_html_entities(word))
+    }
+
+    /// Render word as language span, linked to its dictionary entry, with gloss as tooltip
+    ///
+    /// `word` is html-escaped. Words not in dictionary are not linked
+    pub fn to_html(&self, word: &str) -> String {
+        let text = Format::Text(String::new()).format(word);
+        let entry = match self.find(word) {
+            Some(entry) => entry,
+            None => return text,
+        };
+
+        let gloss = entry.gloss(self.gloss_lang);
+        let tooltip = match &entry.pos {
+            Some(pos) => format!("{}: {}", pos, gloss),
+            None => gloss,
+        };
+        format!(
+            r#"<a class="link lookup" href="{}#{}" title="{}"> {} </a>"#,
+            self.href,
+            entry.id,
+            html_escape::encode_double_quoted_attribute(&tooltip),
+            text
+        )
+    }
+}
+
+/// Render headword, senses, etymology and derived words of entry, with link to page of entry, if
+/// it has one
+///
+/// `link` is href of other entry of dictionary
+pub fn entry_to_html(
+    entry: &Entry,
+    dict: &Dictionary,
+    gloss_lang: &str,
+    translations: &[(String, String)],
+    link: impl Fn(&Entry) -> String,
+    permalink: Option<&str>,
+) -> String {
+    let mut head = vec![Format::Text(String::new()).format(&escape_html(&entry.headword))];
+    if let Some(ipa) = &entry.ipa {
+        head.push(Format::BroadIPA.format(&escape_html(ipa)));
+    }
+    if let Some(pos) = &entry.pos {
+        head.push(format!(
+            r#"<span class="pos"> {} </span>"#,
+            escape_html(pos)
+        ));
+    }
+    if let Some(permalink) = permalink {
+        head.push(format!(
+            r#"<a class="link permalink" href="{}" title="Permalink"> ¶ </a>"#,
+            permalink
+        ));
+    }
+
+    let senses = entry
+        .senses
+        .iter()
+        .map(|sense| sense_to_html(sense, gloss_lang, translations))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    // Link to other entry, or only word if not in dictionary
+    let word_link = |word: &str| {
+        let text = Format::Text(String::new()).format(&escape_html(word));
+        match dict.source(word) {
+            Some(other) => format!(r#"<a class="link" href="{}"> {} </a>"#, link(other), text),
+            None => text,
+        }
+    };
+
+    let mut etymology = entry
+        .etymology
+        .sources()
+        .filter(|(field, _)| *field != "compound")
+        .map(|(field, word)| format!("{} {}", field, word_link(word)))
+        .collect::<Vec<_>>();
+    if !entry.etymology.compound.is_empty() {
+        etymology.push(format!(
+            "compound of {}",
+            entry
+                .etymology
+                .compound
+                .iter()
+                .map(|word| word_link(word))
+                .collect::<Vec<_>>()
+                .join(" + ")
+        ));
+    }
+    if let Some(borrowed) = &entry.etymology.borrowed {
+        etymology.push(format!("borrowed from {}", escape_html(borrowed)));
+    }
+    let etymology = if etymology.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "<p class=\"etymology\"> Etymology: {} </p>\n",
+            etymology.join("; ")
+        )
+    };
+
+    let derived = dict.derived(entry);
+    let derived = if derived.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "<p class=\"derived\"> Derived: {} </p>\n",
+            derived
+                .iter()
+                .map(|other| format!(
+                    r#"<a class="link" href="{}"> {} </a>"#,
+                    link(other),
+                    Format::Text(String::new()).format(&escape_html(&other.headword))
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    };
+
+    format!(
+        "<dt class=\"entry\" id=\"{id}\"> {head} </dt>\n\
+        <dd class=\"entry-body\">\n\
+            <ol class=\"senses\">\n{senses}\n</ol>\n\
+            {notes}{etymology}{derived}\
+        </dd>",
+        id = entry.id,
+        head = head.join(" "),
+        notes = notes_to_html(&entry.notes),
+    )
+}
+
+fn sense_to_html(sense: &Sense, gloss_lang: &str, translations: &[(String, String)]) -> String {
+    let glosses = sense
+        .glosses
+        .iter()
+        .map(|gloss| {
+            if gloss.lang == gloss_lang {
+                format!(r#"<span class="gloss"> {} </span>"#, escape_html(&gloss.text))
+            } else {
+                let name = translations
+                    .iter()
+                    .find(|(code, _)| *code == gloss.lang)
+                    .map_or(gloss.lang.as_str(), |(_, name)| name.as_str());
+                format!(
+                    r#"<span class="gloss other" lang="{}"><span class="name"> {} </span> {} </span>"#,
+                    gloss.lang,
+                    escape_html(name),
+                    escape_html(&gloss.text)
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let examples = if sense.examples.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "<ul class=\"examples\">\n{}\n</ul>\n",
+            sense
+                .examples
+                .iter()
+                .map(|example| {
+                    format!(
+                        r#"<li class="example"> {} {} </li>"#,
+                        Format::Text(String::new()).format(&escape_html(&example.text)),
+                        example
+                            .translation
+                            .as_ref()
+                            .map(|translation| format!(
+                                r#"<span class="translation"> {} </span>"#,
+                                escape_html(translation)
+                            ))
+                            .unwrap_or_default()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    };
+
+    format!(
+        "<li class=\"sense\"> {}\n{}{}</li>",
+        glosses,
+        examples,
+        notes_to_html(&sense.notes)
+    )
+}
+
+fn notes_to_html(notes: &[String]) -> String {
+    notes
+        .iter()
+        .map(|note| format!("<p class=\"note\"> {} </p>\n", escape_html(note)))
+        .collect()
+}
+
+/// Id of letter heading
+fn letter_id(letter: &str) -> String {
+    format!("letter-{}", letter.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::OrthographyConfig, diagnostic::Diagnostics};
+
+    fn dict() -> Dictionary {
+        Dictionary::parse(
+            "kata\n  pos: noun\n  see: mila\n  - cat\nkata\n  - cut\nmila\n  - love\n",
+            "dict.ldct",
+            "en",
+            &[],
+            &mut Diagnostics::default(),
+        )
+    }
+
+    #[test]
+    fn entry_page_has_sections() {
+        let dict = dict();
+        let page = entry_page_to_html(
+            &dict.entries[0],
+            &dict,
+            "en",
+            &[],
+            &[("Grammar".to_string(), "../grammar.html#nouns".to_string())],
+            "../dictionary.html",
+        );
+
+        assert!(page
+            .body
+            .contains(r#"<h2 class="header" id="related"> Related words </h2>"#));
+        assert!(page.body.contains(r#"<a class="link" href="kata-2.html">"#));
+        assert!(page.body.contains(r#"<span class="gloss"> cut </span>"#));
+        assert!(page.body.contains(r#"<a class="link" href="mila.html">"#));
+        assert!(page
+            .body
+            .contains(r#"<li> <a class="link" href="../grammar.html#nouns"> Grammar </a> </li>"#));
+        assert!(page.body.contains(
+            r#"<p> <a class="link" href="../dictionary.html#kata"> Dictionary </a> </p>"#
+        ));
+        assert_eq!(
+            page.headings
+                .iter()
+                .map(|heading| heading.id.as_str())
+                .collect::<Vec<_>>(),
+            ["related", "mentions"]
+        );
+
+        let page = entry_page_to_html(&dict.entries[2], &dict, "en", &[], &[], "dictionary.html");
+        assert!(!page.body.contains("Related words"));
+        assert!(!page.body.contains("Mentioned in"));
+        assert!(page.headings.is_empty());
+    }
+
+    #[test]
+    fn letters_are_grouped() {
+        let orthography = Orthography::new(&OrthographyConfig {
+            alphabet: ["a", "k", "m", "z"]
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            ignore: vec![],
+        });
+        let words = ["kata", "kiki", "mila", "xu"];
+        let page = letters_to_html(
+            &words,
+            &orthography,
+            |word| word,
+            |word| format!("<dt> {} </dt>", word),
+            "words",
+        );
+
+        assert!(page.body.contains(
+            r##"<nav class="dict-letters"> <span class="link empty"> A </span> <a class="link" href="#letter-k"> K </a> <a class="link" href="#letter-m"> M </a> <span class="link empty"> Z </span> <a class="link" href="#letter-x"> X </a> </nav>"##
+        ));
+        assert!(page.body.contains(
+            "<h2 class=\"header letter\" id=\"letter-k\"> K </h2>\n<dl class=\"words\">\n<dt> kata </dt>\n<dt> kiki </dt>\n</dl>"
+        ));
+        assert_eq!(
+            page.headings
+                .iter()
+                .map(|heading| heading.text.as_str())
+                .collect::<Vec<_>>(),
+            ["K", "M", "X"]
+        );
+    }
+
+    #[test]
+    fn lookup_links_words() {
+        let dict = dict();
+        let lookup = Lookup {
+            dictionary: &dict,
+            gloss_lang: "en",
+            href: "dictionary.html".to_string(),
+        };
+
+        let html = lookup.to_html("kata");
+        assert!(html.starts_with(
+            r#"<a class="link lookup" href="dictionary.html#kata" title="noun: cat">"#
+        ));
+        assert!(lookup
+            .to_html("mila")
+            .contains(r#"href="dictionary.html#mila" title="love""#));
+        assert!(!lookup.to_html("nomi").contains("<a"));
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use html_escape::encode_text as escape_html;
+
+use super::{page::letters_to_html, slug, Dictionary, Entry, Sense};
+use crate::{
+    convert::{Document, Format},
+    orthography::Orthography,
+};
+
+/// Words which are not keywords, if no stopwords are given for English
+const STOPWORDS_EN: &[&str] = &[
+    "a",
+    "an",
+    "the",
+    "to",
+    "of",
+    "in",
+    "on",
+    "at",
+    "for",
+    "with",
+    "by",
+    "from",
+    "be",
+    "or",
+    "and",
+    "something",
+    "someone",
+    "oneself",
+    "one's",
+];
+
+/// Key of reverse dictionary, with senses which have it
+struct ReverseEntry<'a> {
+    key: String,
+    id: String,
+    senses: Vec<(&'a Entry, &'a Sense)>,
+}
+
+/// Render reverse dictionary page for language, sorted by keyword, linking to entries of dictionary
+///
+/// `href` is path of dictionary page, relative to reverse dictionary page
+pub fn reverse_to_html(
+    dict: &Dictionary,
+    lang: &str,
+    stopwords: Option<&[String]>,
+    href: &str,
+) -> Document {
+    let stopwords = language_stopwords(lang, stopwords);
+
+    let mut entries = Vec::<ReverseEntry>::new();
+    // Index of each key in entries
+    let mut indexes = HashMap::<String, usize>::new();
+    for entry in &dict.entries {
+        for sense in &entry.senses {
+            for key in sense_keys(sense, lang, &stopwords) {
+                match indexes.get(&key) {
+                    Some(&index) => entries[index].senses.push((entry, sense)),
+                    None => {
+                        indexes.insert(key.clone(), entries.len());
+                        entries.push(ReverseEntry {
+                            id: String::new(),
+                            key,
+                            senses: vec![(entry, sense)],
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    // Natural language is sorted in Unicode order
+    let orthography = Orthography::default();
+    orthography.sort_by_word(&mut entries, |entry| &entry.key);
+
+    // Unique id from each key, like ids of dictionary entries
+    let mut counts = HashMap::<String, usize>::new();
+    for entry in &mut entries {
+        let id = slug(&entry.key);
+        let count = counts.entry(id.clone()).or_insert(0);
+        *count += 1;
+        entry.id = if *count > 1 {
+            format!("{}-{}", id, count)
+        } else {
+            id
+        };
+    }
+
+    letters_to_html(
+        &entries,
+        &orthography,
+        |entry| &entry.key,
+        |entry| reverse_entry_to_html(entry, lang, href),
+        "dict reverse",
+    )
+}
+
+/// Stopwords given for language, or default stopwords for English
+pub(super) fn language_stopwords<'a>(lang: &str, stopwords: Option<&'a [String]>) -> Vec<&'a str> {
+    match stopwords {
+        Some(stopwords) => stopwords.iter().map(String::as_str).collect(),
+        None if lang == "en" => STOPWORDS_EN.to_vec(),
+        None => Vec::new(),
+    }
+}
+
+/// Explicit keys of sense in language, or keywords of gloss
+pub(super) fn sense_keys(sense: &Sense, lang: &str, stopwords: &[&str]) -> Vec<String> {
+    let keys = sense
+        .keys
+        .iter()
+        .filter(|key| key.lang == lang)
+        .map(|key| key.text.to_lowercase())
+        .collect::<Vec<_>>();
+    if !keys.is_empty() {
+        return keys;
+    }
+
+    let mut keys = Vec::new();
+    for key in sense
+        .gloss(lang)
+        .map_or(Vec::new(), |gloss| keywords(gloss, stopwords))
+    {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+/// Keywords of gloss, such as `cat`, `small` and `feline` from `cat; small (wild) feline`
+///
+/// Each word of a phrase which is not a stopword is a keyword, or every word if all are stopwords
+pub(super) fn keywords(gloss: &str, stopwords: &[&str]) -> Vec<String> {
+    phrases(gloss, stopwords).into_iter().flatten().collect()
+}
+
+/// Words of each phrase of gloss, without stopwords unless every word is a stopword
+///
+/// Gloss is split at `;` and `,`, and text in brackets is removed
+pub(super) fn phrases(gloss: &str, stopwords: &[&str]) -> Vec<Vec<String>> {
+    let mut gloss_no_brackets = String::new();
+    let mut depth = 0;
+    for ch in gloss.chars() {
+        match ch {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            _ if depth == 0 => gloss_no_brackets.push(ch),
+            _ => (),
+        }
+    }
+
+    let mut phrases = Vec::new();
+    for phrase in gloss_no_brackets.split([';', ',']) {
+        let words = phrase
+            .split_whitespace()
+            .map(|word| {
+                word.trim_matches(|ch: char| !ch.is_alphanumeric())
+                    .to_lowercase()
+            })
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>();
+
+        let content = words
+            .iter()
+            .filter(|word| !stopwords.contains(&word.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if content.is_empty() {
+            phrases.push(words);
+        } else {
+            phrases.push(content);
+        }
+    }
+    phrases.retain(|words| !words.is_empty());
+    phrases
+}
+
+fn reverse_entry_to_html(entry: &ReverseEntry, lang: &str, href: &str) -> String {
+    let senses = entry
+        .senses
+        .iter()
+        .map(|(entry, sense)| {
+            format!(
+                r#"<li class="sense"> <a class="link" href="{}#{}"> {} </a> <span class="gloss"> {} </span> </li>"#,
+                href,
+                entry.id,
+                Format::Text(String::new()).format(&escape_html(&entry.headword)),
+                escape_html(sense.gloss(lang).unwrap_or_default())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "<dt class=\"entry\" id=\"{}\"> <span class=\"key\"> {} </span> </dt>\n\
+        <dd class=\"entry-body\">\n<ul class=\"senses\">\n{}\n</ul>\n</dd>",
+        entry.id,
+        escape_html(&entry.key),
+        senses
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverse_ids_are_unique() {
+        let mut diagnostics = crate::diagnostic::Diagnostics::default();
+        let dict = Dictionary::parse(
+            "a\n  - x\n    key: ice cream\nb\n  - x\n    key: ice-cream, ?, !\n",
+            "dict.ldct",
+            "en",
+            &[],
+            &mut diagnostics,
+        );
+        let body = reverse_to_html(&dict, "en", None, "dict.html").body;
+
+        for id in ["ice-cream", "ice-cream-2", "entry", "entry-2"] {
+            assert!(body.contains(&format!("id=\"{}\"", id)), "{}", id);
+        }
+    }
+
+    #[test]
+    fn keywords_works() {
+        assert_eq!(keywords("cat", STOPWORDS_EN), vec!["cat"]);
+        assert_eq!(keywords("to purr", STOPWORDS_EN), vec!["purr"]);
+        assert_eq!(
+            keywords("cat; small (wild) feline, the", STOPWORDS_EN),
+            vec!["cat", "small", "feline", "the"]
+        );
+        assert_eq!(
+            keywords("piece of bread", &[]),
+            vec!["piece", "of", "bread"]
+        );
+    }
+}
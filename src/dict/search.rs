@@ -0,0 +1,78 @@
+use serde_json::json;
+
+use super::Dictionary;
+use crate::orthography::Orthography;
+
+/// Script for dictionary search, using index from `search-index.js`
+pub const SEARCH_SCRIPT: &str = include_str!("search.js");
+
+/// Search box for dictionary page, with scripts, which are in same directory as page
+pub const SEARCH_BOX: &str = r#"<div class="dict-search">
+<input type="search" class="dict-search-input" placeholder="Search" aria-label="Search dictionary" />
+<ol class="dict-search-results"></ol>
+</div>
+<script src="search-index.js"></script>
+<script src="search.js"></script>"#;
+
+/// Search index of dictionary as compact JSON, with entries in collation order
+///
+/// Each entry is `[headword, normalized headword, id, part of speech, gloss]`. `page` is path of
+/// dictionary page, relative to index
+pub fn search_index(
+    dict: &Dictionary,
+    orthography: &Orthography,
+    gloss_lang: &str,
+    page: &str,
+) -> String {
+    let mut entries = dict.entries.iter().collect::<Vec<_>>();
+    orthography.sort_by_word(&mut entries, |entry| &entry.headword);
+
+    json!({
+        "page": page,
+        "ignore": orthography.ignored(),
+        "entries": entries
+            .iter()
+            .map(|entry| {
+                json!([
+                    entry.headword,
+                    orthography.normalize(&entry.headword),
+                    entry.id,
+                    entry.pos.as_deref().unwrap_or_default(),
+                    entry.gloss(gloss_lang),
+                ])
+            })
+            .collect::<Vec<_>>(),
+    })
+    .to_string()
+}
+
+/// Search index as script, which can be loaded from `file://` pages
+pub fn search_index_script(index: &str) -> String {
+    format!("var LINGDOCS_SEARCH_INDEX = {};\n", index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::OrthographyConfig, diagnostic::Diagnostics};
+
+    #[test]
+    fn search_index_works() {
+        let dict = Dictionary::parse(
+            "ŝalo\n  pos: noun\n  - scarf\nkata\n  - cat\n  - kitten\n",
+            "dict.ldct",
+            "en",
+            &[],
+            &mut Diagnostics::default(),
+        );
+        let orthography = Orthography::new(&OrthographyConfig {
+            alphabet: vec!["a".to_string(), "k".to_string(), "ŝ".to_string()],
+            ignore: vec![],
+        });
+
+        assert_eq!(
+            search_index(&dict, &orthography, "en", "dict.html"),
+            r#"{"page":"dict.html","ignore":[],"entries":[["kata","kata","kata","","cat; kitten"],["ŝalo","salo","ŝalo","noun","scarf"]]}"#
+        );
+    }
+}
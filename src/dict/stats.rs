@@ -0,0 +1,275 @@
+use std::fmt;
+
+use html_escape::encode_text as escape_html;
+use serde::Serialize;
+
+use super::Dictionary;
+use crate::{
+    convert::{Document, Heading},
+    ipa,
+    orthography::Orthography,
+};
+
+/// Counts of lexicon
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub entries: usize,
+    pub senses: usize,
+    /// Entries with each part of speech, most common first, with `None` for entries without
+    pub parts_of_speech: Vec<(Option<String>, usize)>,
+    /// Letters in headwords, most common first
+    pub letters: Vec<(String, usize)>,
+    /// Phonemes in IPA of entries, most common first
+    pub phonemes: Vec<(String, usize)>,
+    /// Average syllables of entries with IPA
+    pub average_syllables: Option<f64>,
+    /// Change since previous version of dictionary, if given
+    pub growth: Option<Growth>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Growth {
+    /// Entries in previous version
+    pub previous: usize,
+    pub added: usize,
+    pub changed: usize,
+    pub removed: usize,
+}
+
+impl Stats {
+    /// Count entries, parts of speech, letters of headwords and phonemes of IPA
+    ///
+    /// Growth is compared with `previous` dictionary, by id
+    pub fn new(
+        dict: &Dictionary,
+        orthography: &Orthography,
+        previous: Option<&Dictionary>,
+        gloss_lang: &str,
+    ) -> Self {
+        let mut parts_of_speech = Vec::new();
+        let mut letters = Vec::new();
+        let mut phonemes = Vec::new();
+        let mut syllables = Vec::new();
+
+        for entry in &dict.entries {
+            count(&mut parts_of_speech, entry.pos.clone());
+            for letter in orthography.letters(&entry.headword) {
+                count(&mut letters, letter);
+            }
+            if let Some(ipa) = &entry.ipa {
+                for phoneme in ipa::phonemes(ipa) {
+                    count(&mut phonemes, phoneme);
+                }
+                syllables.push(ipa::count_syllables(ipa));
+            }
+        }
+
+        // Ties are in collation order, or Unicode order
+        letters.sort_by_cached_key(|(letter, n)| (usize::MAX - n, orthography.sort_key(letter)));
+        phonemes.sort_by(|(a, a_n), (b, b_n)| b_n.cmp(a_n).then(a.cmp(b)));
+        parts_of_speech.sort_by(|(a, a_n), (b, b_n)| b_n.cmp(a_n).then(a.cmp(b)));
+
+        Stats {
+            entries: dict.entries.len(),
+            senses: dict.entries.iter().map(|entry| entry.senses.len()).sum(),
+            parts_of_speech,
+            letters,
+            phonemes,
+            average_syllables: if syllables.is_empty() {
+                None
+            } else {
+                Some(syllables.iter().sum::<usize>() as f64 / syllables.len() as f64)
+            },
+            growth: previous.map(|previous| {
+                let diff = previous.diff(dict, gloss_lang);
+                Growth {
+                    previous: previous.entries.len(),
+                    added: diff.added.len(),
+                    changed: diff.changed.len(),
+                    removed: diff.removed.len(),
+                }
+            }),
+        }
+    }
+
+    /// Counts of parts of speech, with `(none)` for entries without
+    fn pos_counts(&self) -> Vec<(String, usize)> {
+        self.parts_of_speech
+            .iter()
+            .map(|(pos, n)| (pos.as_deref().unwrap_or("(none)").to_string(), *n))
+            .collect()
+    }
+
+    /// Render stats as page, with table for each count
+    pub fn to_html(&self) -> Document {
+        let mut body = vec![format!(
+            "<p class=\"stats-summary\"> {} entries, {} senses{} </p>",
+            self.entries,
+            self.senses,
+            self.average_syllables
+                .map(|average| format!(", {:.2} syllables per word", average))
+                .unwrap_or_default()
+        )];
+        let mut headings = Vec::new();
+
+        let pos = self.pos_counts();
+        for (title, id, counts) in [
+            ("Parts of speech", "parts-of-speech", &pos),
+            ("Letters", "letters", &self.letters),
+            ("Phonemes", "phonemes", &self.phonemes),
+        ] {
+            if counts.is_empty() {
+                continue;
+            }
+            body.push(format!(
+                r#"<h2 class="header" id="{}"> {} </h2>"#,
+                id, title
+            ));
+            headings.push(Heading {
+                level: 1,
+                text: title.to_string(),
+                id: id.to_string(),
+            });
+
+            let total = counts.iter().map(|(_, n)| n).sum();
+            body.push("<table class=\"stats\">".to_string());
+            for (item, n) in counts {
+                body.push(format!(
+                    "<tr> <td> {} </td> <td> {} </td> <td> {} </td> </tr>",
+                    escape_html(item),
+                    n,
+                    percent(*n, total)
+                ));
+            }
+            body.push("</table>".to_string());
+        }
+
+        Document {
+            body: body.join("\n"),
+            headings,
+            mentions: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    /// Stats as text, for terminal
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Entries: {}", self.entries)?;
+        writeln!(f, "Senses: {}", self.senses)?;
+        if let Some(average) = self.average_syllables {
+            writeln!(f, "Average syllables: {:.2}", average)?;
+        }
+
+        let pos = self.pos_counts();
+        for (title, counts) in [
+            ("Parts of speech", &pos),
+            ("Letters", &self.letters),
+            ("Phonemes", &self.phonemes),
+        ] {
+            if counts.is_empty() {
+                continue;
+            }
+            writeln!(f, "\n{}:", title)?;
+            let total = counts.iter().map(|(_, n)| n).sum();
+            let width = counts
+                .iter()
+                .map(|(item, _)| item.chars().count())
+                .max()
+                .unwrap_or(0);
+            for (item, n) in counts {
+                writeln!(
+                    f,
+                    "  {:width$}  {:>5}  {:>6}",
+                    item,
+                    n,
+                    percent(*n, total),
+                    width = width
+                )?;
+            }
+        }
+
+        if let Some(growth) = &self.growth {
+            writeln!(f, "\nGrowth:")?;
+            writeln!(
+                f,
+                "  {} -> {} entries ({:+})",
+                growth.previous,
+                self.entries,
+                self.entries as i64 - growth.previous as i64
+            )?;
+            writeln!(
+                f,
+                "  {} added, {} changed, {} removed",
+                growth.added, growth.changed, growth.removed
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Add one to count of item
+fn count<T: PartialEq>(counts: &mut Vec<(T, usize)>, item: T) {
+    match counts.iter_mut().find(|(other, _)| *other == item) {
+        Some((_, n)) => *n += 1,
+        None => counts.push((item, 1)),
+    }
+}
+
+fn percent(n: usize, total: usize) -> String {
+    format!("{:.1}%", n as f64 * 100.0 / total.max(1) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::OrthographyConfig, diagnostic::Diagnostics};
+
+    #[test]
+    fn stats_works() {
+        let parse =
+            |file| Dictionary::parse(file, "dict.ldct", "en", &[], &mut Diagnostics::default());
+        let previous = parse("kata\n  - cat\nmila\n  - love\n");
+        let dict = parse(
+            "kata\n  ipa: /ˈka.ta/\n  pos: noun\n  - cat\n  - kitten\nngata\n  ipa: /ˈŋa.ta/\n  pos: noun\n  - goose\nnomi\n  pos: verb\n  - name\n",
+        );
+        let orthography = Orthography::new(&OrthographyConfig {
+            alphabet: ["a", "g", "i", "k", "m", "n", "ng", "o", "t"]
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            ignore: vec![],
+        });
+
+        let stats = Stats::new(&dict, &orthography, Some(&previous), "en");
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.senses, 4);
+        assert_eq!(
+            stats.parts_of_speech,
+            vec![(Some("noun".to_string()), 2), (Some("verb".to_string()), 1)]
+        );
+        assert_eq!(
+            stats.letters[..4],
+            [
+                ("a".to_string(), 4),
+                ("t".to_string(), 2),
+                ("i".to_string(), 1),
+                ("k".to_string(), 1),
+            ]
+        );
+        assert_eq!(stats.phonemes[0], ("a".to_string(), 4));
+        assert_eq!(stats.average_syllables, Some(2.0));
+
+        let growth = stats.growth.as_ref().unwrap();
+        assert_eq!(
+            (
+                growth.previous,
+                growth.added,
+                growth.changed,
+                growth.removed
+            ),
+            (2, 2, 1, 1)
+        );
+        assert!(stats.to_string().contains("  2 -> 3 entries (+1)\n"));
+    }
+}
@@ -0,0 +1,532 @@
+use std::error::Error;
+
+use super::{parse_example, parse_keys, split_list, DictFormat, Dictionary, Entry, Gloss, Sense};
+use crate::{Config, MyError};
+
+/// Column of table, where each row is one sense of an entry
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Id,
+    Headword,
+    Ipa,
+    Pos,
+    Note,
+    /// Related headwords, separated with commas
+    See,
+    Root,
+    From,
+    /// Parts of compound, separated with commas
+    Compound,
+    Borrowed,
+    /// Gloss in language
+    Gloss(String),
+    /// Reverse dictionary keys in language
+    Key(String),
+    Example,
+    SenseNote,
+}
+
+impl Field {
+    /// Field from name, such as `gloss`, `eo`, or `key.eo`
+    fn from_name(name: &str, gloss_lang: &str, langs: &[String]) -> Option<Self> {
+        let is_lang = |lang: &str| langs.iter().any(|other| other == lang);
+
+        Some(match name {
+            "id" => Field::Id,
+            "headword" => Field::Headword,
+            "ipa" => Field::Ipa,
+            "pos" => Field::Pos,
+            "note" => Field::Note,
+            "see" => Field::See,
+            "root" => Field::Root,
+            "from" => Field::From,
+            "compound" => Field::Compound,
+            "borrowed" => Field::Borrowed,
+            "gloss" => Field::Gloss(gloss_lang.to_string()),
+            "key" => Field::Key(gloss_lang.to_string()),
+            "example" => Field::Example,
+            "sense_note" => Field::SenseNote,
+            lang if is_lang(lang) => Field::Gloss(lang.to_string()),
+            _ => match name.strip_prefix("key.") {
+                Some(lang) if is_lang(lang) => Field::Key(lang.to_string()),
+                _ => return None,
+            },
+        })
+    }
+
+    fn name(&self, gloss_lang: &str) -> String {
+        match self {
+            Field::Id => "id".to_string(),
+            Field::Headword => "headword".to_string(),
+            Field::Ipa => "ipa".to_string(),
+            Field::Pos => "pos".to_string(),
+            Field::Note => "note".to_string(),
+            Field::See => "see".to_string(),
+            Field::Root => "root".to_string(),
+            Field::From => "from".to_string(),
+            Field::Compound => "compound".to_string(),
+            Field::Borrowed => "borrowed".to_string(),
+            Field::Gloss(lang) if lang == gloss_lang => "gloss".to_string(),
+            Field::Gloss(lang) => lang.clone(),
+            Field::Key(lang) if lang == gloss_lang => "key".to_string(),
+            Field::Key(lang) => format!("key.{}", lang),
+            Field::Example => "example".to_string(),
+            Field::SenseNote => "sense_note".to_string(),
+        }
+    }
+
+    /// Value of field for sense of entry
+    ///
+    /// Fields with many values are separated with linebreaks, or commas for keys and related words
+    fn get(&self, entry: &Entry, sense: &Sense) -> String {
+        match self {
+            // Slug, if it gives the same id, so that it is kept on import
+            Field::Id => match &entry.slug {
+                Some(slug) if super::slug(slug) == entry.id => slug.clone(),
+                _ => entry.id.clone(),
+            },
+            Field::Headword => entry.headword.clone(),
+            Field::Ipa => entry.ipa.clone().unwrap_or_default(),
+            Field::Pos => entry.pos.clone().unwrap_or_default(),
+            Field::Note => entry.notes.join("\n"),
+            Field::See => entry.see.join(", "),
+            Field::Root => entry.etymology.root.clone().unwrap_or_default(),
+            Field::From => entry.etymology.from.clone().unwrap_or_default(),
+            Field::Compound => entry.etymology.compound.join(", "),
+            Field::Borrowed => entry.etymology.borrowed.clone().unwrap_or_default(),
+            Field::Gloss(lang) => sense.gloss(lang).unwrap_or_default().to_string(),
+            Field::Key(lang) => sense
+                .keys
+                .iter()
+                .filter(|key| key.lang == *lang)
+                .map(|key| key.text.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            Field::Example => sense
+                .examples
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Field::SenseNote => sense.notes.join("\n"),
+        }
+    }
+
+    /// Set field of entry or sense from non-empty value
+    ///
+    /// Fields of entry are only set by first row of entry
+    fn set(&self, entry: &mut Entry, sense: &mut Sense, value: &str, is_first_row: bool) {
+        let lines = || value.lines().map(str::trim).filter(|line| !line.is_empty());
+
+        match self {
+            Field::Id | Field::Headword => (),
+            Field::Ipa if is_first_row => entry.ipa = Some(value.trim_matches('/').to_string()),
+            Field::Pos if is_first_row => entry.pos = Some(value.to_string()),
+            Field::Note if is_first_row => entry.notes.extend(lines().map(String::from)),
+            Field::See if is_first_row => entry.see.extend(split_list(value)),
+            Field::Root if is_first_row => entry.etymology.root = Some(value.to_string()),
+            Field::From if is_first_row => entry.etymology.from = Some(value.to_string()),
+            Field::Compound if is_first_row => entry.etymology.compound.extend(split_list(value)),
+            Field::Borrowed if is_first_row => entry.etymology.borrowed = Some(value.to_string()),
+            Field::Ipa
+            | Field::Pos
+            | Field::Note
+            | Field::See
+            | Field::Root
+            | Field::From
+            | Field::Compound
+            | Field::Borrowed => (),
+            Field::Gloss(lang) => {
+                match sense.glosses.iter_mut().find(|gloss| gloss.lang == *lang) {
+                    Some(gloss) => gloss.text = value.to_string(),
+                    None => sense.glosses.push(Gloss {
+                        lang: lang.clone(),
+                        text: value.to_string(),
+                    }),
+                }
+            }
+            Field::Key(lang) => sense.keys.extend(parse_keys(lang, value)),
+            Field::Example => sense.examples.extend(lines().map(parse_example)),
+            Field::SenseNote => sense.notes.extend(lines().map(String::from)),
+        }
+    }
+}
+
+/// Fields of table, with column name of each
+fn columns(config: &Config) -> Result<Vec<(Field, String)>, Box<dyn Error>> {
+    let gloss_lang = config.gloss_language();
+    let langs = config.translation_codes();
+    let options = &config.dictionary.table;
+
+    let fields = if options.fields.is_empty() {
+        let mut fields = vec![
+            Field::Id,
+            Field::Headword,
+            Field::Ipa,
+            Field::Pos,
+            Field::Gloss(gloss_lang.to_string()),
+        ];
+        for lang in langs.iter().filter(|lang| *lang != gloss_lang) {
+            fields.push(Field::Gloss(lang.clone()));
+        }
+        fields.extend([
+            Field::Key(gloss_lang.to_string()),
+            Field::Example,
+            Field::SenseNote,
+            Field::Note,
+            Field::See,
+            Field::Root,
+            Field::From,
+            Field::Compound,
+            Field::Borrowed,
+        ]);
+        fields
+    } else {
+        options
+            .fields
+            .iter()
+            .map(|name| {
+                Field::from_name(name, gloss_lang, &langs)
+                    .ok_or_else(|| MyError(format!("Unknown dictionary field `{}`", name)))
+            })
+            .collect::<Result<_, _>>()?
+    };
+
+    Ok(fields
+        .into_iter()
+        .map(|field| {
+            let column = column_name(&field, config);
+            (field, column)
+        })
+        .collect())
+}
+
+/// Column name of field, from `dictionary.table.columns` or field name
+fn column_name(field: &Field, config: &Config) -> String {
+    let name = field.name(config.gloss_language());
+    let columns = &config.dictionary.table.columns;
+    columns.get(&name).cloned().unwrap_or(name)
+}
+
+/// Write dictionary as table, with one row for each sense
+///
+/// Format is csv, tsv, or json. If fields have no id and adjacent entries have the same headword, an
+/// id column is added first, so that they are not read as one entry
+pub(super) fn write_table(
+    dict: &Dictionary,
+    format: DictFormat,
+    config: &Config,
+) -> Result<String, Box<dyn Error>> {
+    let mut columns = columns(config)?;
+    let has_homographs = dict
+        .entries
+        .windows(2)
+        .any(|pair| pair[0].headword == pair[1].headword);
+    if has_homographs && !columns.iter().any(|(field, _)| *field == Field::Id) {
+        columns.insert(0, (Field::Id, column_name(&Field::Id, config)));
+    }
+    let rows = dict
+        .entries
+        .iter()
+        .flat_map(|entry| entry.senses.iter().map(move |sense| (entry, sense)))
+        .map(|(entry, sense)| {
+            columns
+                .iter()
+                .map(|(field, _)| field.get(entry, sense))
+                .collect::<Vec<_>>()
+        });
+
+    match format {
+        DictFormat::Csv | DictFormat::Tsv => {
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(delimiter(format))
+                .from_writer(Vec::new());
+            writer.write_record(columns.iter().map(|(_, column)| column))?;
+            for row in rows {
+                writer.write_record(row)?;
+            }
+            Ok(String::from_utf8(writer.into_inner()?)?)
+        }
+
+        DictFormat::Json => {
+            let rows = rows
+                .map(|row| {
+                    columns
+                        .iter()
+                        .zip(row)
+                        .filter(|(_, value)| !value.is_empty())
+                        .map(|((_, column), value)| (column.clone(), value.into()))
+                        .collect::<serde_json::Map<_, _>>()
+                })
+                .collect::<Vec<_>>();
+            Ok(serde_json::to_string_pretty(&rows)? + "\n")
+        }
+
+        DictFormat::Lift => Err(Box::new(not_table(format))),
+    }
+}
+
+/// Read dictionary from csv, tsv, or json table, with one row for each sense
+///
+/// Rows with same id are senses of same entry, or rows with same headword if id is empty or table has
+/// no id column. A row with empty headword is another sense of previous entry
+pub(super) fn read_table(
+    file: &str,
+    format: DictFormat,
+    config: &Config,
+) -> Result<Dictionary, Box<dyn Error>> {
+    let (header, rows) = match format {
+        DictFormat::Csv | DictFormat::Tsv => read_csv(file, delimiter(format))?,
+        DictFormat::Json => read_json(file)?,
+        DictFormat::Lift => return Err(Box::new(not_table(format))),
+    };
+
+    let mut columns = columns(config)?;
+    // Id column can be added by export
+    if !columns.iter().any(|(field, _)| *field == Field::Id) {
+        columns.push((Field::Id, column_name(&Field::Id, config)));
+    }
+    let fields = header
+        .iter()
+        .map(|name| {
+            columns
+                .iter()
+                .find(|(_, column)| column == name)
+                .map(|(field, _)| field.clone())
+                .ok_or_else(|| MyError(format!("Unknown column `{}`", name)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let headword_col = fields
+        .iter()
+        .position(|field| *field == Field::Headword)
+        .ok_or_else(|| MyError("Table has no headword column".to_string()))?;
+    let id_col = fields.iter().position(|field| *field == Field::Id);
+
+    let gloss_lang = config.gloss_language();
+    let mut entries = Vec::<Entry>::new();
+    // Id of current entry, if table has id column
+    let mut last_id = None;
+    // Id of each entry, if not empty
+    let mut ids = Vec::new();
+
+    for (row_num, row) in rows.iter().enumerate() {
+        // Header is first line
+        let line = row_num + 2;
+        let cell = |col: usize| row.get(col).map(|value| value.trim()).unwrap_or_default();
+
+        let headword = cell(headword_col);
+        let id = id_col.map(cell);
+        let is_new_entry = match entries.last() {
+            None => true,
+            Some(_) if headword.is_empty() => false,
+            Some(last) => match id.filter(|id| !id.is_empty()) {
+                Some(id) => last_id != Some(id),
+                None => last.headword != headword,
+            },
+        };
+
+        if is_new_entry {
+            if headword.is_empty() {
+                return Err(Box::new(MyError(format!("Row {}: Missing headword", line))));
+            }
+            entries.push(Entry {
+                headword: headword.to_string(),
+                line,
+                ..Default::default()
+            });
+            last_id = id;
+            ids.push(id.filter(|id| !id.is_empty()));
+        }
+        let entry = entries.last_mut().expect("Entry should exist");
+
+        let mut sense = Sense {
+            glosses: vec![Gloss {
+                lang: gloss_lang.to_string(),
+                text: String::new(),
+            }],
+            line,
+            ..Default::default()
+        };
+        for (col, field) in fields.iter().enumerate() {
+            let value = cell(col);
+            if !value.is_empty() {
+                field.set(entry, &mut sense, value, is_new_entry);
+            }
+        }
+        entry.senses.push(sense);
+    }
+
+    // Ids which are not from headword, such as `kata` or `kata-2`, are from `id` field of entry
+    for (entry, id) in entries.iter_mut().zip(ids) {
+        let base = super::slug(&entry.headword);
+        let is_from_headword = |id: &str| {
+            id == base
+                || id
+                    .strip_prefix(&base)
+                    .and_then(|rest| rest.strip_prefix('-'))
+                    .is_some_and(|count| count.parse::<usize>().is_ok())
+        };
+        entry.slug = id.filter(|id| !is_from_headword(id)).map(String::from);
+    }
+
+    Ok(Dictionary::new(entries))
+}
+
+type Rows = (Vec<String>, Vec<Vec<String>>);
+
+fn not_table(format: DictFormat) -> MyError {
+    MyError(format!("{:?} is not a table format", format))
+}
+
+fn delimiter(format: DictFormat) -> u8 {
+    match format {
+        DictFormat::Tsv => b'\t',
+        _ => b',',
+    }
+}
+
+/// Read header and rows of csv or tsv file
+fn read_csv(file: &str, delimiter: u8) -> Result<Rows, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(file.as_bytes());
+
+    let header = reader.headers()?.iter().map(String::from).collect();
+    let rows = reader
+        .records()
+        .map(|record| Ok(record?.iter().map(String::from).collect()))
+        .collect::<Result<_, csv::Error>>()?;
+    Ok((header, rows))
+}
+
+/// Read header and rows of json array of objects
+///
+/// Header has every key of objects, in order of first use
+fn read_json(file: &str) -> Result<Rows, Box<dyn Error>> {
+    let objects: Vec<serde_json::Map<String, serde_json::Value>> = serde_json::from_str(file)
+        .map_err(|err| MyError(format!("Expected array of objects: {}", err)))?;
+
+    let mut header = Vec::<String>::new();
+    for object in &objects {
+        for key in object.keys() {
+            if !header.contains(key) {
+                header.push(key.clone());
+            }
+        }
+    }
+
+    let rows = objects
+        .iter()
+        .map(|object| {
+            header
+                .iter()
+                .map(|key| match object.get(key) {
+                    Some(serde_json::Value::String(value)) => value.clone(),
+                    Some(serde_json::Value::Null) | None => String::new(),
+                    Some(value) => value.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+    Ok((header, rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Diagnostics;
+
+    const DICT: &str = "kata\n  ipa: /ˈka.ta/\n  pos: noun\n  note: Informal\n  - cat, feline\n    eo: kato\n    key: cat\n    ex: kata mi | my cat\n    ex: kata\n  - to purr\n\npita\n  - bread\n";
+
+    fn config() -> Config {
+        Config::from(
+            "[package]\nname = \"abc\"\ncode = \"ab\"\ntranslations = [[\"en\", \"english\"], [\"eo\", \"esperanto\"]]",
+        )
+        .unwrap()
+    }
+
+    fn parse(file: &str) -> Dictionary {
+        let mut diagnostics = Diagnostics::default();
+        let dict = Dictionary::parse(
+            file,
+            "dict.ldct",
+            "en",
+            &["en".to_string(), "eo".to_string()],
+            &mut diagnostics,
+        );
+        assert!(diagnostics.report().is_ok());
+        dict
+    }
+
+    #[test]
+    fn tables_round_trip() {
+        let config = config();
+        let file = format!(
+            "{}\nkata\n  id: Kite Toy\n  - kite\n\nkata\n  - hat\n  - cap\n",
+            DICT
+        );
+        let dict = parse(&file);
+        assert_eq!(dict.to_ldct("en"), file);
+
+        for format in [DictFormat::Csv, DictFormat::Tsv, DictFormat::Json] {
+            let table = write_table(&dict, format, &config).unwrap();
+            let imported = read_table(&table, format, &config).unwrap();
+            assert_eq!(imported.to_ldct("en"), file, "{:?}", format);
+            assert!(dict
+                .diff(&imported, "en")
+                .to_string()
+                .starts_with("0 added"));
+        }
+    }
+
+    #[test]
+    fn homographs_are_not_merged() {
+        let mut config = config();
+        let dict = parse("bela\n  - beautiful\n\nbela\n  - night\n  - evening\n");
+
+        for fields in [vec![], vec!["headword".to_string(), "gloss".to_string()]] {
+            config.dictionary.table.fields = fields;
+            let table = write_table(&dict, DictFormat::Csv, &config).unwrap();
+            assert!(table.starts_with("id,headword,"));
+            assert!(table.contains("\nbela-2,bela,"));
+
+            let imported = read_table(&table, DictFormat::Csv, &config).unwrap();
+            assert_eq!(imported.to_ldct("en"), dict.to_ldct("en"));
+            assert_eq!(
+                dict.diff(&imported, "en").to_string(),
+                "0 added, 0 changed, 0 removed"
+            );
+        }
+    }
+
+    #[test]
+    fn read_table_works() {
+        let mut config = config();
+        config
+            .dictionary
+            .table
+            .columns
+            .insert("headword".to_string(), "Word".to_string());
+
+        let dict = read_table(
+            "Word,gloss,pos\nkata,cat,noun\n,kitten,\npita,bread,noun\nlupa,wolf,\n",
+            DictFormat::Csv,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(
+            dict.to_ldct("en"),
+            "kata\n  pos: noun\n  - cat\n  - kitten\n\npita\n  pos: noun\n  - bread\n\nlupa\n  - wolf\n"
+        );
+
+        let diff = parse(DICT).diff(&dict, "en");
+        assert_eq!(diff.added, vec!["lupa"]);
+        assert_eq!(diff.changed, vec!["kata", "pita"]);
+        assert!(diff.removed.is_empty());
+
+        assert!(read_table("word\nkata\n", DictFormat::Csv, &config).is_err());
+        assert!(read_table("<lift/>", DictFormat::Lift, &config).is_err());
+    }
+}
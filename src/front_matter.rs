@@ -0,0 +1,81 @@
+use serde::Deserialize;
+
+/// Delimiter line around front matter
+const DELIMITER: &str = "+++";
+
+/// Metadata at top of `.ling` file, in TOML, between `+++` lines
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrontMatter {
+    /// Overrides first header as page title
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Position in navigation, lowest first
+    pub order: Option<i64>,
+    /// Name of layout template
+    pub layout: Option<String>,
+    pub tags: Vec<String>,
+    /// Drafts are not built, unless `options.drafts` is set
+    pub draft: bool,
+    /// Language of glosses of `.llst` concept list
+    pub lang: Option<String>,
+}
+
+/// Separate front matter from rest of `.ling` file
+///
+/// Returns default front matter if file does not start with `+++` line
+///
+/// Rest of file has front matter lines replaced with blank lines, to preserve line numbers
+pub fn split_front_matter(file: &str) -> Result<(FrontMatter, String), String> {
+    let file = file.replace("\r\n", "\n");
+
+    let mut lines = file.split('\n');
+    if lines.next().map(str::trim_end) != Some(DELIMITER) {
+        return Ok((FrontMatter::default(), file));
+    }
+
+    let mut toml = Vec::new();
+    for line in lines.by_ref() {
+        if line.trim_end() == DELIMITER {
+            let front_matter = toml::from_str(&toml.join("\n"))
+                .map_err(|err| format!("invalid front matter: {}", err))?;
+
+            // Delimiter lines and toml lines
+            let blank = "\n".repeat(toml.len() + 2);
+            let rest = lines.collect::<Vec<_>>().join("\n");
+
+            return Ok((front_matter, blank + &rest));
+        }
+        toml.push(line);
+    }
+
+    Err(format!(
+        "front matter is never closed with `{}` line",
+        DELIMITER
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_front_matter_works() {
+        let (front, rest) = split_front_matter("# abc\ndef").unwrap();
+        assert_eq!(front.title, None);
+        assert_eq!(rest, "# abc\ndef");
+
+        let (front, rest) = split_front_matter(
+            "+++\ntitle = \"Abc\"\norder = 2\ntags = [\"x\"]\ndraft = true\n+++\n# abc",
+        )
+        .unwrap();
+        assert_eq!(front.title.as_deref(), Some("Abc"));
+        assert_eq!(front.order, Some(2));
+        assert_eq!(front.tags, vec!["x"]);
+        assert!(front.draft);
+        assert_eq!(rest, "\n\n\n\n\n\n# abc");
+
+        assert!(split_front_matter("+++\ntitle = \"Abc\"\n# abc").is_err());
+        assert!(split_front_matter("+++\nunknown = 1\n+++\n").is_err());
+    }
+}
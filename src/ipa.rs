@@ -0,0 +1,89 @@
+use unicode_normalization::char::is_combining_mark;
+
+/// IPA vowels, for counting syllables
+const VOWELS: &str = "aeiouyɑɐɒæɛɜɝəɘɚɞɤɨɪʉʊʌʏøœɶɯɵ";
+
+/// Combining mark of syllabic consonant
+const SYLLABIC: char = '\u{0329}';
+
+/// Phonemes of IPA transcription, with diacritics, length marks and tie bars kept with their letter
+///
+/// Stress marks, syllable breaks and spaces are skipped
+pub fn phonemes(ipa: &str) -> Vec<String> {
+    let mut phonemes = Vec::<String>::new();
+    let mut tied = false;
+
+    for ch in ipa.chars() {
+        if matches!(ch, 'ˈ' | 'ˌ' | '.' | '|' | '‖' | '/' | '[' | ']') || ch.is_whitespace() {
+            tied = false;
+            continue;
+        }
+        match phonemes.last_mut() {
+            Some(last) if is_modifier(ch) || tied => last.push(ch),
+            _ => phonemes.push(ch.to_string()),
+        }
+        // Next letter is part of same phoneme, such as `t͡s`
+        tied = matches!(ch, '\u{0361}' | '\u{035C}');
+    }
+
+    phonemes
+}
+
+/// Syllables of IPA transcription, as groups of vowels and syllabic consonants
+///
+/// Vowels separated with a syllable break or stress mark are different syllables
+pub fn count_syllables(ipa: &str) -> usize {
+    let mut syllables = 0;
+    let mut in_nucleus = false;
+
+    for ch in ipa.chars() {
+        if matches!(ch, 'ˈ' | 'ˌ' | '.') || ch.is_whitespace() {
+            in_nucleus = false;
+        } else if ch == SYLLABIC {
+            // Consonant before mark was not counted
+            if !in_nucleus {
+                syllables += 1;
+            }
+            in_nucleus = true;
+        } else if VOWELS.contains(ch) {
+            if !in_nucleus {
+                syllables += 1;
+            }
+            in_nucleus = true;
+        } else if !is_modifier(ch) {
+            in_nucleus = false;
+        }
+    }
+
+    syllables
+}
+
+/// Whether character is diacritic or modifier letter, which is part of letter before
+pub fn is_modifier(ch: char) -> bool {
+    !matches!(ch, 'ˈ' | 'ˌ')
+        && (is_combining_mark(ch)
+            || ('\u{02B0}'..='\u{02FF}').contains(&ch)
+            || ('\u{1D2C}'..='\u{1D6A}').contains(&ch)
+            || ch == 'ⁿ')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phonemes_works() {
+        assert_eq!(
+            phonemes("ˈt͡saː.ŋʷi ˌkʰã"),
+            vec!["t͡s", "aː", "ŋʷ", "i", "kʰ", "ã"]
+        );
+    }
+
+    #[test]
+    fn count_syllables_works() {
+        assert_eq!(count_syllables("ˈka.ta"), 2);
+        assert_eq!(count_syllables("ˈkai.ti.a"), 3);
+        assert_eq!(count_syllables("ˈbʊtn̩"), 2);
+        assert_eq!(count_syllables("st"), 0);
+    }
+}
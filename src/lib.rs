@@ -2,11 +2,33 @@ mod case;
 mod compile;
 mod config;
 mod convert;
+mod diagnostic;
+mod dict;
+mod front_matter;
+mod ipa;
+mod macros;
+mod orthography;
+mod phonotactics;
+mod preprocess;
+mod rewrite;
+mod sound_change;
+mod template;
 mod utils;
+mod variables;
+mod word_list;
 
-use std::{error::Error, fmt, fs};
+use std::{error::Error, fmt, fs, path::Path};
 
-pub use crate::{compile::compile, config::Config};
+pub use crate::{compile::compile, config::Config, dict::DictFormat};
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    dict::Dictionary,
+    front_matter::split_front_matter,
+    orthography::Orthography,
+    sound_change::SoundChanges,
+    word_list::{Column, WordList},
+};
 
 #[derive(Debug)]
 pub struct MyError(String);
@@ -17,16 +39,258 @@ impl fmt::Display for MyError {
     }
 }
 
-pub fn run(dir: &str) -> Result<(), Box<dyn Error>> {
-    let file =
-        fs::read_to_string(format!("{}/Lingdocs.toml", dir)).expect("Could not read config file");
+/// Read config file in project directory, optionally with build profile
+///
+/// Source and build paths are made relative to project directory
+pub fn load_config(dir: &str, profile: Option<&str>) -> Result<Config, Box<dyn Error>> {
+    let file = fs::read_to_string(format!("{}/Lingdocs.toml", dir))
+        .map_err(|err| MyError(format!("Could not read config file: {}", err)))?;
 
-    let mut config = Config::from(&file).expect("Could not parse config file");
+    let mut config = match profile {
+        Some(profile) => Config::from_profile(&file, profile),
+        None => Config::from(&file).map_err(Into::into),
+    }
+    .map_err(|err| MyError(format!("Could not parse config file: {}", err)))?;
 
     config.files.source = format!("{}/{}", dir, config.files.source);
     config.files.build = format!("{}/{}", dir, config.files.build);
 
-    compile(config).expect("Could not compile");
+    Ok(config)
+}
+
+pub fn run(dir: &str, profile: Option<&str>) -> Result<(), Box<dyn Error>> {
+    compile(load_config(dir, profile)?)
+}
+
+/// Parse dictionary file of project, or error if it does not exist or has errors
+fn read_dictionary(config: &Config) -> Result<Dictionary, Box<dyn Error>> {
+    let mut diagnostics = Diagnostics::default();
+    let dictionary = Dictionary::load(config, &mut diagnostics).ok_or_else(|| {
+        MyError(format!(
+            "Could not find dictionary file `{}`",
+            config.files.dict
+        ))
+    })?;
+    diagnostics.report()?;
+    Ok(dictionary)
+}
+
+/// Print statistics of dictionary of project, as text or JSON
+///
+/// `previous` is an export of dictionary in any format, to compare with
+pub fn stats(dir: &str, json: bool, previous: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let config = load_config(dir, None)?;
+    let dictionary = read_dictionary(&config)?;
+    let previous = match previous {
+        Some(path) => {
+            let format = DictFormat::from_path(path)
+                .ok_or_else(|| MyError(format!("Unknown dictionary format of `{}`", path)))?;
+            let file = fs::read_to_string(path)
+                .map_err(|err| MyError(format!("Could not read file: {}", err)))?;
+            Some(dict::import(&file, format, &config)?)
+        }
+        None => None,
+    };
+
+    let stats = dict::Stats::new(
+        &dictionary,
+        &Orthography::new(&config.orthography),
+        previous.as_ref(),
+        config.gloss_language(),
+    );
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        print!("{}", stats);
+    }
+    Ok(())
+}
+
+/// Print concepts of concept lists without entries in dictionary of project
+///
+/// Lists are names of built-in lists or `.llst` files, default is `dictionary.concept_lists`
+pub fn coverage(dir: &str, lists: &[String]) -> Result<(), Box<dyn Error>> {
+    let config = load_config(dir, None)?;
+    let dictionary = read_dictionary(&config)?;
+    let lists = if lists.is_empty() {
+        &config.dictionary.concept_lists
+    } else {
+        lists
+    };
+
+    for (i, name) in lists.iter().enumerate() {
+        let mut diagnostics = Diagnostics::default();
+        let list = dict::ConceptList::load(
+            name,
+            &config.files.source,
+            config.gloss_language(),
+            &mut diagnostics,
+        )?;
+        diagnostics.report()?;
+        let stopwords = config.dictionary.stopwords.get(&list.lang);
+        let coverage = dictionary.coverage(&list, stopwords.map(Vec::as_slice))?;
+        if i > 0 {
+            println!();
+        }
+        print!("{}", coverage);
+    }
+    Ok(())
+}
+
+/// Apply sound changes of project to words of word list, and write word list with form after each
+/// stage, or trace of each word, to file or stdout
+///
+/// `rules` is name of file of sound changes, needed if project has more than one
+pub fn sca(
+    dir: &str,
+    file: &str,
+    rules: Option<&str>,
+    flags: &[String],
+    trace: bool,
+    output: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let config = load_config(dir, None)?;
+    let mut diagnostics = Diagnostics::default();
+    let sound_changes = SoundChanges::load_all(&config, &mut diagnostics);
+    diagnostics.report()?;
+
+    let sound_changes = match (rules, sound_changes.as_slice()) {
+        (None, [sound_changes]) => sound_changes,
+        (None, []) => {
+            return Err(MyError(format!(
+                "Could not find sound changes in `{}`",
+                config.files.sound_changes
+            ))
+            .into())
+        }
+        (None, _) => {
+            return Err(MyError(
+                "Project has more than one file of sound changes, use `--rules`".to_string(),
+            )
+            .into())
+        }
+        (Some(name), _) => sound_changes
+            .iter()
+            .find(|sound_changes| sound_changes.name == name)
+            .ok_or_else(|| MyError(format!("Could not find sound changes `{}`", name)))?,
+    };
+
+    let list_file = fs::read_to_string(file)
+        .map_err(|err| MyError(format!("Could not read word list: {}", err)))?;
+    let (_, list_file) =
+        split_front_matter(&list_file).map_err(|err| MyError(format!("{}: {}", file, err)))?;
+    let list = WordList::parse(&list_file, file, &mut diagnostics);
+    diagnostics.report()?;
+
+    let flags = [config.options.flags.as_slice(), flags].concat();
+    let derived = if trace {
+        let word = list
+            .columns
+            .iter()
+            .position(|column| *column == Column::Word)
+            .unwrap_or(0);
+        list.rows
+            .iter()
+            .filter_map(|row| row.get(word))
+            .map(|word| {
+                sound_changes
+                    .derive(word.trim_start_matches('*'), &flags)
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        sound_changes.derive_list(&list, &flags).to_llst()
+    };
 
+    match output {
+        Some(output) => fs::write(output, derived)?,
+        None => print!("{}", derived),
+    }
+    Ok(())
+}
+
+/// Write dictionary of project in other format, to file or stdout
+pub fn dict_export(
+    dir: &str,
+    format: DictFormat,
+    output: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let config = load_config(dir, None)?;
+    let exported = dict::export(&read_dictionary(&config)?, format, &config)?;
+
+    match output {
+        Some(output) => fs::write(output, exported)?,
+        None => print!("{}", exported),
+    }
+    Ok(())
+}
+
+/// Write derivation graph of dictionary of project in DOT format, to file or stdout
+pub fn dict_graph(dir: &str, output: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let config = load_config(dir, None)?;
+    let dot = read_dictionary(&config)?.etymology_dot();
+
+    match output {
+        Some(output) => fs::write(output, dot)?,
+        None => print!("{}", dot),
+    }
+    Ok(())
+}
+
+/// Replace dictionary of project with file in other format, and print added, changed, and removed entries
+///
+/// Dictionary with comments is only replaced with `force`, as comments are not kept
+pub fn dict_import(
+    dir: &str,
+    file: &str,
+    format: Option<DictFormat>,
+    dry_run: bool,
+    force: bool,
+) -> Result<(), Box<dyn Error>> {
+    let config = load_config(dir, None)?;
+    let format = format
+        .or_else(|| DictFormat::from_path(file))
+        .ok_or_else(|| MyError("Unknown dictionary format, use `--format`".to_string()))?;
+
+    let file =
+        fs::read_to_string(file).map_err(|err| MyError(format!("Could not read file: {}", err)))?;
+    let dictionary = dict::import(&file, format, &config)?;
+
+    let path = format!("{}/{}", config.files.source, config.files.dict);
+    let old = if Path::new(&path).exists() {
+        read_dictionary(&config)?
+    } else {
+        Dictionary::default()
+    };
+    let gloss_lang = config.gloss_language();
+    println!("{}", old.diff(&dictionary, gloss_lang));
+
+    let comments = fs::read_to_string(&path)
+        .map(|file| {
+            file.lines()
+                .filter(|line| line.trim_start().starts_with('#'))
+                .count()
+        })
+        .unwrap_or_default();
+    if comments > 0 && dry_run {
+        eprintln!(
+            "{}",
+            Diagnostic::warning(
+                &config.files.dict,
+                None,
+                format!("{} comment lines would be removed by import", comments),
+            )
+        );
+    } else if comments > 0 && !force {
+        return Err(Box::new(MyError(format!(
+            "Import would remove {} comment lines of `{}`, use `--force` to replace it",
+            comments, config.files.dict
+        ))));
+    }
+
+    if !dry_run {
+        fs::write(path, dictionary.to_ldct(gloss_lang))?;
+    }
     Ok(())
 }
@@ -0,0 +1,590 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    utils::find_closing_brace,
+    variables, Config,
+};
+
+/// Maximum depth of macros calling other macros
+const MAX_DEPTH: usize = 32;
+
+/// Macro which expands to `.ling` source
+///
+/// Called with `{!name positional | other | named = value}`
+#[derive(Debug)]
+pub struct Macro {
+    pub params: Vec<Param>,
+    /// Source, with `$param` for each parameter
+    pub body: String,
+    /// Where macro was defined, such as `macros.ling:4`
+    pub location: String,
+    /// Line of definition in macros file, if defined there
+    pub line: Option<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Param {
+    pub name: String,
+    /// Value if argument is not given, otherwise argument is required
+    pub default: Option<String>,
+}
+
+pub type Macros = BTreeMap<String, Macro>;
+
+/// Load macros from `[macros]` table in config, and from macros file in source directory
+///
+/// Variables in macro bodies are replaced when loaded
+pub fn load_macros(
+    config: &Config,
+    vars: &BTreeMap<String, String>,
+    diagnostics: &mut Diagnostics,
+) -> Macros {
+    let mut macros = Macros::new();
+
+    for (name, def) in &config.macros {
+        let location = format!("Lingdocs.toml: [macros.{}]", name);
+        let params = def.params.iter().map(|param| parse_param(param)).collect();
+        macros.insert(
+            name.clone(),
+            Macro {
+                params,
+                body: variables::substitute(&def.body, &location, vars, diagnostics),
+                location,
+                line: None,
+            },
+        );
+    }
+
+    let path = Path::new(&config.files.source).join(&config.files.macros);
+    if path.exists() {
+        let file = fs::read_to_string(path).expect("Could not read macros file");
+        let file = variables::substitute(&file, &config.files.macros, vars, diagnostics);
+        for (name, def) in parse_macros_file(&file, &config.files.macros, diagnostics) {
+            if let Some(other) = macros.get(&name) {
+                diagnostics.push(
+                    Diagnostic::error(
+                        &config.files.macros,
+                        def.line,
+                        format!("macro `{}` is defined twice", name),
+                    )
+                    .with_note(format!("{}: first defined here", other.location)),
+                );
+            }
+            macros.insert(name, def);
+        }
+    }
+
+    macros
+}
+
+/// Parse macro definitions from file
+///
+/// ```txt
+/// @macro word(word, ipa, gloss = )
+/// {' $word} {/ $ipa} *$gloss*
+/// @end
+/// ```
+pub fn parse_macros_file(
+    file: &str,
+    filepath: &str,
+    diagnostics: &mut Diagnostics,
+) -> Vec<(String, Macro)> {
+    let mut macros = Vec::new();
+    // Name, params, body lines, and line number of current definition
+    let mut current: Option<(String, Vec<Param>, Vec<&str>, usize)> = None;
+
+    for (line_num, line) in file.lines().enumerate() {
+        let line_num = line_num + 1;
+
+        if let Some((name, params, body, start)) = &mut current {
+            if line.trim() == "@end" {
+                macros.push((
+                    std::mem::take(name),
+                    Macro {
+                        params: std::mem::take(params),
+                        body: body.join("\n"),
+                        location: format!("{}:{}", filepath, start),
+                        line: Some(*start),
+                    },
+                ));
+                current = None;
+            } else {
+                body.push(line);
+            }
+            continue;
+        }
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let header = line
+            .strip_prefix("@macro ")
+            .and_then(|header| header.trim().strip_suffix(')'))
+            .and_then(|header| header.split_once('('));
+        match header {
+            Some((name, params)) if is_ident(name.trim()) => {
+                let params = params
+                    .split(',')
+                    .filter(|param| !param.trim().is_empty())
+                    .map(parse_param)
+                    .collect();
+                current = Some((name.trim().to_string(), params, Vec::new(), line_num));
+            }
+            _ => diagnostics.push(Diagnostic::error(
+                filepath,
+                Some(line_num),
+                "expected `@macro name(params)`",
+            )),
+        }
+    }
+
+    if let Some((name, _, _, start)) = current {
+        diagnostics.push(Diagnostic::error(
+            filepath,
+            Some(start),
+            format!("macro `{}` is never closed with `@end`", name),
+        ));
+    }
+
+    macros
+}
+
+/// Parse parameter, such as `name` or `name = default`
+fn parse_param(param: &str) -> Param {
+    match param.split_once('=') {
+        Some((name, default)) => Param {
+            name: name.trim().to_string(),
+            default: Some(unquote(default.trim()).to_string()),
+        },
+        None => Param {
+            name: param.trim().to_string(),
+            default: None,
+        },
+    }
+}
+
+/// Remove double quotes from around string, if any
+fn unquote(string: &str) -> &str {
+    string
+        .strip_prefix('"')
+        .and_then(|string| string.strip_suffix('"'))
+        .unwrap_or(string)
+}
+
+/// Macro name, only alphanumeric characters, `_`, and `-`
+fn is_ident(string: &str) -> bool {
+    !string.is_empty()
+        && string
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-')
+}
+
+/// Expand all `{!name args}` macro calls in `.ling` file, with line of file for each line of
+/// expanded text
+///
+/// Lines of expansion of a call have line of call. Calls which cannot be expanded are reported,
+/// and left unchanged
+pub fn expand(
+    file: &str,
+    filepath: &str,
+    macros: &Macros,
+    diagnostics: &mut Diagnostics,
+) -> (String, Vec<usize>) {
+    let mut line = 1;
+    let mut stack = Vec::new();
+    let mut expander = Expander {
+        macros,
+        filepath,
+        diagnostics,
+        lines: vec![1],
+    };
+    let expanded = expander.expand(file, &mut line, &mut stack);
+    (expanded, expander.lines)
+}
+
+struct Expander<'a> {
+    macros: &'a Macros,
+    filepath: &'a str,
+    diagnostics: &'a mut Diagnostics,
+    /// Line of file for each line of expanded text
+    lines: Vec<usize>,
+}
+
+impl<'a> Expander<'a> {
+    /// Expand calls in text
+    ///
+    /// `line` is line of call site in original file, and is only advanced for top level text
+    ///
+    /// `stack` is names of macros currently being expanded
+    fn expand(&mut self, text: &str, line: &mut usize, stack: &mut Vec<&'a str>) -> String {
+        let mut output = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find("{!") {
+            let (before, call) = rest.split_at(start);
+            output.push_str(before);
+            self.advance(before, line, stack);
+
+            let end = match find_closing_brace(call) {
+                Some(end) => end,
+                None => {
+                    self.error(*line, stack, "macro call is never closed with `}`");
+                    output.push_str(call);
+                    self.advance(call, line, stack);
+                    return output;
+                }
+            };
+            let call_text = &call[..=end];
+            rest = &call[end + 1..];
+
+            let expanded = match before.ends_with('\\') {
+                true => None,
+                false => self.expand_call(&call_text[2..end], *line, stack),
+            };
+            match expanded {
+                Some(expanded) => {
+                    output.push_str(&expanded);
+                    if stack.is_empty() {
+                        let call_line = *line;
+                        self.lines.extend(expanded.matches('\n').map(|_| call_line));
+                        *line += call_text.matches('\n').count();
+                    }
+                }
+                None => {
+                    output.push_str(call_text);
+                    self.advance(call_text, line, stack);
+                }
+            }
+        }
+
+        output.push_str(rest);
+        self.advance(rest, line, stack);
+        output
+    }
+
+    /// Advance line past top level text, which is unchanged in expanded text
+    fn advance(&mut self, text: &str, line: &mut usize, stack: &[&str]) {
+        if stack.is_empty() {
+            for _ in text.matches('\n') {
+                *line += 1;
+                self.lines.push(*line);
+            }
+        }
+    }
+
+    /// Expand single call, without surrounding braces and `!`
+    fn expand_call(&mut self, call: &str, line: usize, stack: &mut Vec<&'a str>) -> Option<String> {
+        let name_len = call
+            .find(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '-'))
+            .unwrap_or(call.len());
+        let (name, args) = call.split_at(name_len);
+
+        let (name, def) = match self.macros.get_key_value(name) {
+            Some((name, def)) => (name.as_str(), def),
+            None => {
+                self.error(line, stack, format!("undefined macro `{}`", name));
+                return None;
+            }
+        };
+
+        if stack.contains(&name) {
+            self.error(
+                line,
+                stack,
+                format!("macro `{}` calls itself recursively", name),
+            );
+            return None;
+        }
+        if stack.len() >= MAX_DEPTH {
+            self.error(line, stack, "macros are nested too deeply");
+            return None;
+        }
+
+        let values = match bind_args(def, &split_args(args)) {
+            Ok(values) => values,
+            Err(message) => {
+                self.diagnostics.push(
+                    Diagnostic::error(self.filepath, Some(line), message)
+                        .with_note(format!("{}: macro `{}` defined here", def.location, name)),
+                );
+                return None;
+            }
+        };
+
+        let body = substitute_params(&def.body, &values);
+
+        stack.push(name);
+        let mut inner_line = line;
+        let expanded = self.expand(&body, &mut inner_line, stack);
+        stack.pop();
+
+        Some(expanded)
+    }
+
+    /// Report error at call site, with definition of innermost macro being expanded
+    fn error(&mut self, line: usize, stack: &[&str], message: impl Into<String>) {
+        let mut diagnostic = Diagnostic::error(self.filepath, Some(line), message);
+        if let Some(def) = stack.last().and_then(|name| self.macros.get(*name)) {
+            diagnostic = diagnostic.with_note(format!(
+                "{}: in expansion of macro `{}`",
+                def.location,
+                stack.last().unwrap_or(&"")
+            ));
+        }
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+/// Split arguments at `|`, if not inside nested statement
+fn split_args(args: &str) -> Vec<&str> {
+    let args = args.trim();
+    if args.is_empty() {
+        return Vec::new();
+    }
+
+    let mut split = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut is_escaped = false;
+
+    for (i, ch) in args.char_indices() {
+        if !is_escaped {
+            match ch {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '|' if depth == 0 => {
+                    split.push(args[start..i].trim());
+                    start = i + 1;
+                }
+                _ => (),
+            }
+        }
+        is_escaped = ch == '\\' && !is_escaped;
+    }
+    split.push(args[start..].trim());
+
+    split
+}
+
+/// Match arguments of call with parameters of macro
+///
+/// Arguments are positional, unless given as `name = value`
+fn bind_args<'a>(def: &'a Macro, args: &[&'a str]) -> Result<BTreeMap<&'a str, &'a str>, String> {
+    let mut values = BTreeMap::new();
+    let mut positional = 0;
+
+    for arg in args {
+        let named = arg
+            .split_once('=')
+            .filter(|(name, _)| is_ident(name.trim()));
+
+        if let Some((name, value)) = named {
+            let name = name.trim();
+            if !def.params.iter().any(|param| param.name == name) {
+                return Err(format!("unknown argument `{}`", name));
+            }
+            values.insert(name, value.trim());
+        } else {
+            let param = def.params.get(positional).ok_or_else(|| {
+                format!("too many arguments, expected at most {}", def.params.len())
+            })?;
+            values.insert(param.name.as_str(), *arg);
+            positional += 1;
+        }
+    }
+
+    for param in &def.params {
+        if !values.contains_key(param.name.as_str()) {
+            match &param.default {
+                Some(default) => {
+                    values.insert(&param.name, default);
+                }
+                None => return Err(format!("missing argument `{}`", param.name)),
+            }
+        }
+    }
+
+    Ok(values)
+}
+
+/// Replace `$param` in macro body with argument values
+///
+/// Other `$` characters are unchanged
+fn substitute_params(body: &str, values: &BTreeMap<&str, &str>) -> String {
+    let mut output = String::new();
+    let mut rest = body;
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let name_len = rest
+            .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+            .unwrap_or(rest.len());
+        match values.get(&rest[..name_len]) {
+            Some(value) => {
+                output.push_str(value);
+                rest = &rest[name_len..];
+            }
+            None => output.push('$'),
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_macros() -> Macros {
+        let mut diagnostics = Diagnostics::default();
+        let macros = parse_macros_file(
+            "@macro word(word, ipa, gloss = \"\")\n\
+            {' $word} {/ $ipa} *$gloss*\n\
+            @end\n\
+            \n\
+            @macro pair(a, b)\n\
+            {!word $a | $b}\n\
+            @end\n\
+            \n\
+            @macro loop(x)\n\
+            {!loop $x}\n\
+            @end",
+            "macros.ling",
+            &mut diagnostics,
+        );
+        assert!(diagnostics.report().is_ok());
+        macros.into_iter().collect()
+    }
+
+    #[test]
+    fn parse_macros_file_works() {
+        let macros = test_macros();
+        let word = &macros["word"];
+
+        assert_eq!(
+            word.params,
+            vec![
+                Param {
+                    name: "word".to_string(),
+                    default: None
+                },
+                Param {
+                    name: "ipa".to_string(),
+                    default: None
+                },
+                Param {
+                    name: "gloss".to_string(),
+                    default: Some(String::new())
+                },
+            ]
+        );
+        assert_eq!(word.body, "{' $word} {/ $ipa} *$gloss*");
+        assert_eq!(word.location, "macros.ling:1");
+        assert_eq!(macros["pair"].location, "macros.ling:5");
+    }
+
+    #[test]
+    fn duplicate_macros_are_reported() {
+        let dir = std::env::temp_dir().join(format!("lingdocs-macros-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("macros.ling"),
+            "@macro word()\na\n@end\n\n@macro word()\nb\n@end\n",
+        )
+        .unwrap();
+
+        let mut config =
+            Config::from("[package]\nname = \"abc\"\ncode = \"ab\"\n[macros.word]\nbody = \"c\"")
+                .unwrap();
+        config.files.source = dir.to_string_lossy().to_string();
+        let mut diagnostics = Diagnostics::default();
+        let macros = load_macros(&config, &BTreeMap::new(), &mut diagnostics);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(macros["word"].body, "b");
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "error: macros.ling:1: macro `word` is defined twice\n  \
+                    note: Lingdocs.toml: [macros.word]: first defined here",
+                "error: macros.ling:5: macro `word` is defined twice\n  \
+                    note: macros.ling:1: first defined here",
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_works() {
+        let macros = test_macros();
+        let mut diagnostics = Diagnostics::default();
+
+        assert_eq!(
+            expand(
+                "a {!word kata | ka.ta | gloss = cat} {!pair {'x} | y} \\{!word}",
+                "test.ling",
+                &macros,
+                &mut diagnostics
+            )
+            .0,
+            "a {' kata} {/ ka.ta} *cat* {' {'x}} {/ y} ** \\{!word}"
+        );
+        assert!(diagnostics.report().is_ok());
+    }
+
+    #[test]
+    fn expand_gives_lines_of_file() {
+        let mut diagnostics = Diagnostics::default();
+        let macros = parse_macros_file(
+            "@macro two()\na\nb\nc\n@end",
+            "macros.ling",
+            &mut diagnostics,
+        )
+        .into_iter()
+        .collect();
+
+        let (expanded, lines) = expand(
+            "{!two}\nx\n{!two} y\n{!missing\n}\nz",
+            "test.ling",
+            &macros,
+            &mut diagnostics,
+        );
+        assert_eq!(expanded, "a\nb\nc\nx\na\nb\nc y\n{!missing\n}\nz");
+        assert_eq!(lines, vec![1, 1, 1, 2, 3, 3, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn expand_reports_errors() {
+        let macros = test_macros();
+        let mut diagnostics = Diagnostics::default();
+
+        expand(
+            "\n{!word kata}\n{!loop a}\n{!missing}",
+            "test.ling",
+            &macros,
+            &mut diagnostics,
+        );
+        let messages = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "error: test.ling:2: missing argument `ipa`\n  \
+                    note: macros.ling:1: macro `word` defined here",
+                "error: test.ling:3: macro `loop` calls itself recursively\n  \
+                    note: macros.ling:9: in expansion of macro `loop`",
+                "error: test.ling:4: undefined macro `missing`",
+            ]
+        );
+    }
+}
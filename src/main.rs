@@ -2,11 +2,65 @@ mod args;
 
 use clap::Parser;
 
-use args::Args;
-use lingdocs::run;
+use args::{Args, Command, DictCommand};
+use lingdocs::{coverage, dict_export, dict_graph, dict_import, run, sca, stats};
 
 fn main() {
     let args = Args::parse();
 
-    run(&args.dir.unwrap_or(".".to_string())).expect("Failed");
+    let result = match args.command {
+        Some(Command::Build { dir, profile }) => {
+            run(&dir.unwrap_or(".".to_string()), profile.as_deref())
+        }
+        Some(Command::Stats {
+            dir,
+            json,
+            previous,
+        }) => stats(&dir.unwrap_or(".".to_string()), json, previous.as_deref()),
+        Some(Command::Coverage { dir, list }) => coverage(&dir.unwrap_or(".".to_string()), &list),
+        Some(Command::Sca {
+            file,
+            dir,
+            rules,
+            flag,
+            trace,
+            output,
+        }) => sca(
+            &dir.unwrap_or(".".to_string()),
+            &file,
+            rules.as_deref(),
+            &flag,
+            trace,
+            output.as_deref(),
+        ),
+        Some(Command::Dict { command }) => match command {
+            DictCommand::Export {
+                dir,
+                format,
+                output,
+            } => dict_export(&dir.unwrap_or(".".to_string()), format, output.as_deref()),
+            DictCommand::Import {
+                file,
+                dir,
+                format,
+                dry_run,
+                force,
+            } => dict_import(
+                &dir.unwrap_or(".".to_string()),
+                &file,
+                format,
+                dry_run,
+                force,
+            ),
+            DictCommand::Graph { dir, output } => {
+                dict_graph(&dir.unwrap_or(".".to_string()), output.as_deref())
+            }
+        },
+        None => run(&args.dir.unwrap_or(".".to_string()), None),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,260 @@
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::{
+    case,
+    config::OrthographyConfig,
+    diagnostic::{Diagnostic, Diagnostics},
+};
+
+/// Alphabet and collation order of language
+///
+/// Without declared alphabet, every character is a letter, sorted in Unicode order
+#[derive(Debug, Default)]
+pub struct Orthography {
+    /// Letters in collation order, each with lowercase variants which share its position
+    letters: Vec<Vec<String>>,
+    ignore: Vec<String>,
+}
+
+/// Primary key is position of each letter, secondary key is variant of each letter
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SortKey(Vec<usize>, Vec<usize>);
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// Position and variant of letter
+    Letter(usize, usize),
+    Undeclared(char),
+}
+
+impl Orthography {
+    pub fn new(config: &OrthographyConfig) -> Self {
+        Orthography {
+            letters: config
+                .alphabet
+                .iter()
+                .map(|letter| letter.split_whitespace().map(normalize).collect())
+                .filter(|variants: &Vec<String>| !variants.is_empty())
+                .collect(),
+            ignore: config
+                .ignore
+                .iter()
+                .map(|ignored| normalize(ignored))
+                .filter(|ignored| !ignored.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Split word into letters, longest multigraph first, skipping ignored characters
+    fn tokenize(&self, word: &str) -> Vec<Token> {
+        let word = normalize(word);
+        let mut rest = word.as_str();
+        let mut tokens = Vec::new();
+
+        while let Some(ch) = rest.chars().next() {
+            if let Some(ignored) = self
+                .ignore
+                .iter()
+                .find(|ignored| rest.starts_with(*ignored))
+            {
+                rest = &rest[ignored.len()..];
+                continue;
+            }
+
+            let mut longest: Option<(usize, usize, usize)> = None;
+            for (position, variants) in self.letters.iter().enumerate() {
+                for (variant, text) in variants.iter().enumerate() {
+                    if rest.starts_with(text.as_str())
+                        && longest.is_none_or(|(_, _, len)| text.len() > len)
+                    {
+                        longest = Some((position, variant, text.len()));
+                    }
+                }
+            }
+
+            match longest {
+                Some((position, variant, len)) => {
+                    tokens.push(Token::Letter(position, variant));
+                    rest = &rest[len..];
+                }
+                None => {
+                    tokens.push(Token::Undeclared(ch));
+                    rest = &rest[ch.len_utf8()..];
+                }
+            }
+        }
+
+        tokens
+    }
+
+    /// Key to sort word by, with undeclared letters after all declared letters
+    pub fn sort_key(&self, word: &str) -> SortKey {
+        let (primary, secondary) = self
+            .tokenize(word)
+            .into_iter()
+            .map(|token| match token {
+                Token::Letter(position, variant) => (position, variant),
+                Token::Undeclared(ch) => (self.letters.len() + ch as usize, 0),
+            })
+            .unzip();
+        SortKey(primary, secondary)
+    }
+
+    /// Sort items by word in collation order
+    pub fn sort_by_word<T>(&self, items: &mut [T], word: impl Fn(&T) -> &str) {
+        items.sort_by_cached_key(|item| self.sort_key(word(item)));
+    }
+
+    /// First letter of word, uppercase, for letter headings
+    ///
+    /// Variants of a letter give the first variant
+    pub fn first_letter(&self, word: &str) -> String {
+        match self.tokenize(word).first() {
+            Some(Token::Letter(position, _)) => case::upper_first_once(&self.letters[*position][0]),
+            Some(Token::Undeclared(ch)) => ch.to_uppercase().to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// Every variant of each letter in alphabet, lowercase
+    pub fn variants(&self) -> impl Iterator<Item = &String> {
+        self.letters.iter().flatten()
+    }
+
+    /// First letter of each letter in alphabet, uppercase, in order
+    pub fn letter_headings(&self) -> Vec<String> {
+        self.letters
+            .iter()
+            .map(|variants| case::upper_first_once(&variants[0]))
+            .collect()
+    }
+
+    /// Letters of word, lowercase, with variants of a letter given as its first variant
+    ///
+    /// Ignored characters and whitespace are skipped
+    pub fn letters(&self, word: &str) -> Vec<String> {
+        self.tokenize(word)
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Letter(position, _) => Some(self.letters[position][0].clone()),
+                Token::Undeclared(ch) if ch.is_whitespace() => None,
+                Token::Undeclared(ch) => Some(ch.to_string()),
+            })
+            .collect()
+    }
+
+    /// Characters of word which are not in alphabet, if alphabet is declared
+    pub fn undeclared(&self, word: &str) -> Vec<char> {
+        if self.letters.is_empty() {
+            return Vec::new();
+        }
+
+        let mut undeclared = Vec::new();
+        for token in self.tokenize(word) {
+            if let Token::Undeclared(ch) = token {
+                if !ch.is_whitespace() && !undeclared.contains(&ch) {
+                    undeclared.push(ch);
+                }
+            }
+        }
+        undeclared
+    }
+
+    /// Lowercase word without diacritics or ignored characters, for searching
+    pub fn normalize(&self, word: &str) -> String {
+        let mut word = normalize(word);
+        for ignored in &self.ignore {
+            word = word.replace(ignored.as_str(), "");
+        }
+        word.nfd().filter(|ch| !is_combining_mark(*ch)).collect()
+    }
+
+    /// Characters which are skipped when sorting
+    pub fn ignored(&self) -> &[String] {
+        &self.ignore
+    }
+
+    /// Warn if word has letters which are not in alphabet
+    pub fn check(&self, word: &str, filepath: &str, line: usize, diagnostics: &mut Diagnostics) {
+        let undeclared = self.undeclared(word);
+        if !undeclared.is_empty() {
+            diagnostics.push(Diagnostic::warning(
+                filepath,
+                Some(line),
+                format!(
+                    "word `{}` has letters which are not in alphabet: {}",
+                    word,
+                    undeclared
+                        .iter()
+                        .map(|ch| format!("`{}`", ch))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ));
+        }
+    }
+}
+
+/// Text in lowercase and composed (NFC) form, so letters and ignored characters match words
+fn normalize(text: &str) -> String {
+    text.nfc().collect::<String>().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orthography() -> Orthography {
+        Orthography::new(&OrthographyConfig {
+            alphabet: ["a á", "b", "n", "ng", "o", "z"]
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            ignore: vec!["-".to_string()],
+        })
+    }
+
+    #[test]
+    fn sort_works() {
+        let orthography = orthography();
+        let mut words = vec![
+            "zo", "ngo", "no", "Nz", "ába", "aba", "abb", "b-a", "ax", "ba",
+        ];
+        orthography.sort_by_word(&mut words, |word| word);
+
+        assert_eq!(
+            words,
+            vec!["aba", "ába", "abb", "ax", "b-a", "ba", "no", "Nz", "ngo", "zo"]
+        );
+    }
+
+    #[test]
+    fn letters_work() {
+        let orthography = orthography();
+
+        assert_eq!(orthography.first_letter("ngo"), "Ng");
+        assert_eq!(orthography.first_letter("ába"), "A");
+        assert_eq!(orthography.first_letter("-bo"), "B");
+        assert_eq!(orthography.first_letter("xo"), "X");
+        assert_eq!(orthography.undeclared("xab yx"), vec!['x', 'y']);
+        assert_eq!(
+            orthography.letters("Ánga-x o"),
+            vec!["a", "ng", "a", "x", "o"]
+        );
+        assert_eq!(orthography.normalize("Ŝá-ngo"), "sango");
+        assert_eq!(Orthography::default().undeclared("xyz"), Vec::<char>::new());
+    }
+
+    #[test]
+    fn config_is_normalized() {
+        let orthography = Orthography::new(&OrthographyConfig {
+            alphabet: vec!["A A\u{301}".to_string(), "NG".to_string(), "O".to_string()],
+            ignore: vec!["X".to_string()],
+        });
+
+        assert_eq!(orthography.first_letter("ngo"), "Ng");
+        assert_eq!(orthography.letters("áxngo"), vec!["a", "ng", "o"]);
+        assert_eq!(orthography.letters("a\u{301}ngo"), vec!["a", "ng", "o"]);
+        assert!(orthography.undeclared("Áxo").is_empty());
+    }
+}
@@ -0,0 +1,448 @@
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics, Level},
+    Config,
+};
+
+lazy_static! {
+    static ref RE_CLASS: Regex = Regex::new(r"<(\w+)>").expect("Failed to compile class regex");
+}
+
+/// Phonotactic rules, parsed from `files.phoner`
+///
+/// ```txt
+/// # Classes, with letters separated by spaces
+/// C = p t k m n ng
+/// V = a i u
+///
+/// # Words are (C)V syllables
+/// + ^ (<C>? <V>)+ $
+/// # No vowel clusters
+/// ! <V> <V>
+/// ```
+///
+/// Patterns are regular expressions, without spaces, where `<C>` is any letter of class `C`.
+/// A comment before a rule describes it
+#[derive(Debug)]
+pub struct Phonotactics {
+    /// Path of rules file, relative to source directory
+    pub filepath: String,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    pub kind: RuleKind,
+    /// Pattern as written
+    pub pattern: String,
+    /// Comment before rule
+    pub description: Option<String>,
+    pub line: usize,
+    regex: Regex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleKind {
+    /// Words must match pattern, with `+`
+    Require,
+    /// Words must not match pattern, with `!`
+    Forbid,
+}
+
+impl Phonotactics {
+    /// Parse rules file of project, if it exists
+    pub fn load(config: &Config, diagnostics: &mut Diagnostics) -> Option<Self> {
+        let path = Path::new(&config.files.source).join(&config.files.phoner);
+        if !path.is_file() {
+            return None;
+        }
+
+        let file = fs::read_to_string(path).expect("Could not read phoner file");
+        Some(Phonotactics::parse(
+            &file,
+            &config.files.phoner,
+            diagnostics,
+        ))
+    }
+
+    pub fn parse(file: &str, filepath: &str, diagnostics: &mut Diagnostics) -> Self {
+        // Regex of each class
+        let mut classes = HashMap::<String, String>::new();
+        let mut rules = Vec::new();
+        let mut description = None;
+
+        for (line_num, line) in file.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: String| Diagnostic::error(filepath, Some(line_num + 1), message);
+
+            if line.is_empty() {
+                description = None;
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                description = Some(comment.trim().to_string());
+                continue;
+            }
+
+            let kind = match line.chars().next() {
+                Some('+') => RuleKind::Require,
+                Some('!') => RuleKind::Forbid,
+                _ => {
+                    match line.split_once('=') {
+                        Some((name, letters)) if is_class_name(name.trim()) => {
+                            match class_regex(letters, &classes) {
+                                Ok(regex) => {
+                                    classes.insert(name.trim().to_string(), regex);
+                                }
+                                Err(message) => diagnostics.push(error(message)),
+                            }
+                        }
+                        _ => diagnostics.push(error(
+                            "expected class such as `V = a i u`, or rule starting with `+` or `!`"
+                                .to_string(),
+                        )),
+                    }
+                    description = None;
+                    continue;
+                }
+            };
+
+            let pattern = line[1..].trim();
+            let regex = expand_classes(pattern, &classes).and_then(|regex| {
+                Regex::new(&regex)
+                    .map_err(|err| format!("invalid pattern `{}`: {}", pattern, first_line(&err)))
+            });
+            match regex {
+                Ok(regex) => rules.push(Rule {
+                    kind,
+                    pattern: pattern.to_string(),
+                    description: description.take(),
+                    line: line_num + 1,
+                    regex,
+                }),
+                Err(message) => diagnostics.push(error(message)),
+            }
+        }
+
+        Phonotactics {
+            filepath: filepath.to_string(),
+            rules,
+        }
+    }
+
+    /// First rule which word breaks, if any
+    ///
+    /// Word is compared in lowercase
+    pub fn check(&self, word: &str) -> Option<&Rule> {
+        let word = word.nfc().collect::<String>().to_lowercase();
+        self.rules.iter().find(|rule| {
+            let is_match = rule.regex.is_match(&word);
+            match rule.kind {
+                RuleKind::Require => !is_match,
+                RuleKind::Forbid => is_match,
+            }
+        })
+    }
+
+    /// Report each word of text which breaks a rule
+    ///
+    /// `line` is line number of start of text
+    pub fn check_text(
+        &self,
+        text: &str,
+        filepath: &str,
+        line: usize,
+        level: Level,
+        diagnostics: &mut Diagnostics,
+    ) {
+        for (i, text_line) in text.lines().enumerate() {
+            for word in words(text_line) {
+                if let Some(rule) = self.check(word) {
+                    let message = match rule.kind {
+                        RuleKind::Require => {
+                            format!("word `{}` does not match phonotactic rule `{}`", word, rule)
+                        }
+                        RuleKind::Forbid => format!(
+                            "word `{}` matches forbidden phonotactic rule `{}`",
+                            word, rule
+                        ),
+                    };
+                    diagnostics.push(
+                        Diagnostic::new(level, filepath, Some(line + i), message)
+                            .with_note(self.rule_location(rule)),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Report test words of `{; rule ; words ;}` statement which break a rule, and words marked
+    /// with `*` which do not
+    pub fn check_test(
+        &self,
+        words: &[&str],
+        filepath: &str,
+        line: usize,
+        level: Level,
+        diagnostics: &mut Diagnostics,
+    ) {
+        for word in words {
+            match word.strip_prefix('*') {
+                Some(word) => {
+                    if self.check(word).is_none() {
+                        diagnostics.push(Diagnostic::new(
+                            level,
+                            filepath,
+                            Some(line),
+                            format!(
+                                "word `{}` is marked with `*`, but follows phonotactic rules",
+                                word
+                            ),
+                        ));
+                    }
+                }
+                None => self.check_text(word, filepath, line, level, diagnostics),
+            }
+        }
+    }
+
+    /// Path and line of rule, with its description
+    pub fn rule_location(&self, rule: &Rule) -> String {
+        match &rule.description {
+            Some(description) => {
+                format!("rule at {}:{}: {}", self.filepath, rule.line, description)
+            }
+            None => format!("rule at {}:{}", self.filepath, rule.line),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    /// Rule as written, with `+` or `!`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self.kind {
+            RuleKind::Require => '+',
+            RuleKind::Forbid => '!',
+        };
+        write!(f, "{} {}", symbol, self.pattern)
+    }
+}
+
+/// Rule and test words of `{; rule ; words ;}` statement, without braces, if it has test words
+///
+/// Words marked with `*` are expected to break a rule
+pub fn split_test(statement: &str) -> Option<(&str, Vec<&str>)> {
+    let statement = statement.trim();
+    let statement = statement.strip_suffix(';').unwrap_or(statement);
+    let (rule, words) = statement.split_once(';')?;
+    Some((rule.trim(), words.split_whitespace().collect()))
+}
+
+/// Words of text, without punctuation around them
+pub fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|ch: char| !ch.is_alphanumeric()))
+        .filter(|word| !word.is_empty())
+}
+
+fn is_class_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+}
+
+/// Regex of class, from letters separated by spaces, which can be other classes such as `<V>`
+///
+/// Longer letters are matched first, so multigraphs are not split
+fn class_regex(letters: &str, classes: &HashMap<String, String>) -> Result<String, String> {
+    let mut letters = letters.split_whitespace().collect::<Vec<_>>();
+    if letters.is_empty() {
+        return Err("class has no letters".to_string());
+    }
+    letters.sort_by_key(|letter| usize::MAX - letter.chars().count());
+
+    let letters = letters
+        .iter()
+        .map(|letter| {
+            if RE_CLASS.is_match(letter) {
+                expand_classes(letter, classes)
+            } else {
+                Ok(regex::escape(
+                    &letter.nfc().collect::<String>().to_lowercase(),
+                ))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(format!("(?:{})", letters.join("|")))
+}
+
+/// Pattern without spaces, with `<name>` replaced with regex of class
+fn expand_classes(pattern: &str, classes: &HashMap<String, String>) -> Result<String, String> {
+    let pattern = pattern
+        .split_whitespace()
+        .collect::<String>()
+        .nfc()
+        .collect::<String>();
+    let mut output = String::new();
+    let mut rest = pattern.as_str();
+
+    while let Some(captures) = RE_CLASS.captures(rest) {
+        let (whole, name) = (captures.get(0).unwrap(), &captures[1]);
+        output.push_str(&lowercase_literals(&rest[..whole.start()]));
+        match classes.get(name) {
+            Some(regex) => output.push_str(regex),
+            None => return Err(format!("unknown class `<{}>`", name)),
+        }
+        rest = &rest[whole.end()..];
+    }
+
+    output.push_str(&lowercase_literals(rest));
+    Ok(output)
+}
+
+/// Pattern text in lowercase, as words are matched in lowercase, except escapes such as `\S` and
+/// `\p{Lu}`
+fn lowercase_literals(pattern: &str) -> String {
+    let mut output = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            output.extend(ch.to_lowercase());
+            continue;
+        }
+
+        output.push(ch);
+        let Some(escaped) = chars.next() else {
+            break;
+        };
+        output.push(escaped);
+        if matches!(escaped, 'p' | 'P' | 'x' | 'u' | 'U') {
+            if chars.peek() == Some(&'{') {
+                for ch in chars.by_ref() {
+                    output.push(ch);
+                    if ch == '}' {
+                        break;
+                    }
+                }
+            } else if let Some(ch) = chars.next() {
+                output.push(ch);
+            }
+        }
+    }
+    output
+}
+
+/// First line of regex error, which has pattern and position on other lines
+fn first_line(err: &regex::Error) -> String {
+    let err = err.to_string();
+    err.lines()
+        .rev()
+        .find(|line| line.starts_with("error:"))
+        .map(|line| line.trim_start_matches("error:").trim().to_string())
+        .unwrap_or(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = "\
+        # Letters\n\
+        C = p t k n ng\n\
+        V = a i u\n\
+        L = <C> <V>\n\
+        \n\
+        # Words are (C)V syllables\n\
+        + ^ (<C>? <V>)+ $\n\
+        # No vowel clusters\n\
+        ! <V> <V>\n\
+        ! ^ng\n";
+
+    #[test]
+    fn check_works() {
+        let mut diagnostics = Diagnostics::default();
+        let phonotactics = Phonotactics::parse(RULES, "phoner", &mut diagnostics);
+        assert!(diagnostics.report().is_ok());
+        assert_eq!(phonotactics.rules.len(), 3);
+
+        assert!(phonotactics.check("Kata").is_none());
+        assert!(phonotactics
+            .check("nganu")
+            .is_some_and(|rule| rule.line == 10));
+        assert_eq!(
+            phonotactics.check("kat").map(|rule| rule.to_string()),
+            Some("+ ^ (<C>? <V>)+ $".to_string())
+        );
+        assert_eq!(
+            phonotactics.check("taik").map(|rule| rule.line),
+            Some(7),
+            "first broken rule"
+        );
+        assert!(phonotactics
+            .check("kuanga")
+            .is_some_and(|rule| rule.line == 9));
+
+        phonotactics.check_text(
+            "tika, kaata!\n\nnapa kuu",
+            "index.ling",
+            3,
+            Level::Warning,
+            &mut diagnostics,
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "warning: index.ling:3: word `kaata` matches forbidden phonotactic rule `! <V> <V>`\n  \
+                note: rule at phoner:9: No vowel clusters",
+                "warning: index.ling:5: word `kuu` matches forbidden phonotactic rule `! <V> <V>`\n  \
+                note: rule at phoner:9: No vowel clusters",
+            ]
+        );
+    }
+
+    #[test]
+    fn literals_are_lowercase() {
+        let mut diagnostics = Diagnostics::default();
+        let phonotactics = Phonotactics::parse(
+            "V = a i u\n! ^NG\n! <V>\\S*Ŝ$\n! \\p{Lu}\n",
+            "phoner",
+            &mut diagnostics,
+        );
+        assert!(diagnostics.report().is_ok());
+
+        assert!(phonotactics
+            .check("Ngata")
+            .is_some_and(|rule| rule.line == 2));
+        assert!(phonotactics.check("kaŝ").is_some_and(|rule| rule.line == 3));
+        assert!(phonotactics.check("kata").is_none());
+        assert_eq!(
+            lowercase_literals(r"^NG\B\p{Lu}\PL\x{C5}Ŝ"),
+            r"^ng\B\p{Lu}\PL\x{C5}ŝ"
+        );
+    }
+
+    #[test]
+    fn parse_errors_work() {
+        let mut diagnostics = Diagnostics::default();
+        Phonotactics::parse(
+            "C = p t\nX =\n+ <C><V>\n! (<C>\nnot a rule\n",
+            "phoner",
+            &mut diagnostics,
+        );
+        let messages = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0], "error: phoner:2: class has no letters");
+        assert_eq!(messages[1], "error: phoner:3: unknown class `<V>`");
+        assert!(messages[2].starts_with("error: phoner:4: invalid pattern `(<C>`"));
+        assert!(messages[3].starts_with("error: phoner:5: expected class"));
+    }
+}
@@ -0,0 +1,1039 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use crate::{
+    convert::{escape_primatives, header_id},
+    diagnostic::{Diagnostic, Diagnostics},
+    dict::Dictionary,
+    front_matter::split_front_matter,
+    macros::{self, load_macros, Macros},
+    phonotactics::{self, Phonotactics},
+    rewrite::Rewrite,
+    sound_change::SoundChanges,
+    utils::{find_closing_brace, join_relative},
+    variables::{self, project_variables},
+    word_list::{Column, WordList},
+    Config,
+};
+
+/// Replaces statements in `.ling` files which are expanded before conversion
+///
+/// Conditionals (`{?flag ...}`), then variables (`{$name}`), then macros (`{!name args}`),
+/// then includes (`{<file.ling#id}`, or `{<file.llst}` for word list as table)
+///
+/// Words in `{% word}` statements are checked against dictionary, and test words of `{; rule ; words ;}`
+/// statements against phonotactic rules, as well as words in `{' text}` statements if
+/// `phonotactics.pages` is set
+///
+/// `{= text}` statements are replaced with `{/ ipa}` of transcription of text, and IPA of
+/// `{' text} {/ ipa}` is checked against transcription. `{: ipa}` statements are replaced with
+/// broad and narrow IPA, `{:|}` with table of allophony rules, and `{>name words}` with table of
+/// derivations of words with sound changes, before included files are inserted
+pub struct Preprocessor<'a> {
+    config: &'a Config,
+    dictionary: Option<&'a Dictionary>,
+    phonotactics: Option<&'a Phonotactics>,
+    transcription: Option<&'a Rewrite>,
+    allophony: Option<&'a Rewrite>,
+    sound_changes: &'a [SoundChanges],
+    variables: BTreeMap<String, String>,
+    macros: Macros,
+}
+
+impl<'a> Preprocessor<'a> {
+    pub fn new(
+        config: &'a Config,
+        dictionary: Option<&'a Dictionary>,
+        phonotactics: Option<&'a Phonotactics>,
+        transcription: Option<&'a Rewrite>,
+        allophony: Option<&'a Rewrite>,
+        sound_changes: &'a [SoundChanges],
+        diagnostics: &mut Diagnostics,
+    ) -> Self {
+        let variables = project_variables(config);
+        let macros = load_macros(config, &variables, diagnostics);
+
+        Preprocessor {
+            config,
+            dictionary,
+            phonotactics,
+            transcription,
+            allophony,
+            sound_changes,
+            variables,
+            macros,
+        }
+    }
+
+    /// Expand statements in file, without front matter
+    ///
+    /// `filepath` is relative to source directory
+    pub fn process(&self, file: &str, filepath: &str, diagnostics: &mut Diagnostics) -> String {
+        self.process_nested(file, filepath, &mut vec![filepath.to_string()], diagnostics)
+    }
+
+    /// `stack` is files currently being included, including this one
+    fn process_nested(
+        &self,
+        file: &str,
+        filepath: &str,
+        stack: &mut Vec<String>,
+        diagnostics: &mut Diagnostics,
+    ) -> String {
+        let file = self.expand_conditionals(file, filepath, 1, diagnostics);
+        let file = variables::substitute(&file, filepath, &self.variables, diagnostics);
+        let (file, lines) = macros::expand(&file, filepath, &self.macros, diagnostics);
+        // Diagnostics below have lines of expanded text, which are changed to lines of file
+        let start = diagnostics.count();
+
+        self.check_lookups(&file, filepath, diagnostics);
+        self.check_phonotactics(&file, filepath, diagnostics);
+        self.check_phoner_tests(&file, filepath, diagnostics);
+        self.check_transcriptions(&file, filepath, diagnostics);
+        let file = self.expand_transcriptions(&file, filepath, diagnostics);
+        // Tables and included files are inserted after all are read, so lines of statements are
+        // lines of this file
+        let included = self.read_includes(&file, filepath, stack, diagnostics);
+        let allophones = self.read_allophony(&file, filepath, diagnostics);
+        let derivations = self.read_derivations(&file, filepath, diagnostics);
+
+        diagnostics.map_lines(start, filepath, |line| {
+            lines.get(line - 1).copied().unwrap_or(line)
+        });
+
+        let file = replace_statements(&file, "{:", allophones);
+        let file = replace_statements(&file, "{>", derivations);
+        replace_statements(&file, "{<", included)
+    }
+
+    /// Replace `{?flag ...}` statements with their contents if flag is set, and `{?!flag ...}` if flag
+    /// is not set
+    ///
+    /// Removed statements are replaced with blank lines, to preserve line numbers
+    ///
+    /// `line` is line number of start of text
+    fn expand_conditionals(
+        &self,
+        file: &str,
+        filepath: &str,
+        mut line: usize,
+        diagnostics: &mut Diagnostics,
+    ) -> String {
+        let mut output = String::new();
+        let mut rest = file;
+
+        while let Some(start) = rest.find("{?") {
+            let (before, statement) = rest.split_at(start);
+            output.push_str(before);
+            line += before.matches('\n').count();
+
+            let end = match find_closing_brace(statement) {
+                Some(end) => end,
+                None => {
+                    diagnostics.push(Diagnostic::error(
+                        filepath,
+                        Some(line),
+                        "conditional is never closed with `}`",
+                    ));
+                    rest = statement;
+                    break;
+                }
+            };
+            let statement_text = &statement[..=end];
+            rest = &statement[end + 1..];
+
+            if before.ends_with('\\') {
+                output.push_str(statement_text);
+                line += statement_text.matches('\n').count();
+                continue;
+            }
+
+            let inner = &statement[2..end];
+            let (negate, inner) = match inner.strip_prefix('!') {
+                Some(inner) => (true, inner),
+                None => (false, inner),
+            };
+            let flag_len = inner
+                .find(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '-'))
+                .unwrap_or(inner.len());
+            let (flag, contents) = inner.split_at(flag_len);
+
+            if flag.is_empty() {
+                diagnostics.push(Diagnostic::error(
+                    filepath,
+                    Some(line),
+                    "expected flag name after `{?`",
+                ));
+            }
+
+            let is_set = self.config.options.flags.iter().any(|other| other == flag);
+            if is_set != negate {
+                // Nested conditionals
+                let contents = contents.strip_prefix(' ').unwrap_or(contents);
+                output.push_str(&self.expand_conditionals(contents, filepath, line, diagnostics));
+            } else {
+                output.push_str(&"\n".repeat(statement_text.matches('\n').count()));
+            }
+
+            line += statement_text.matches('\n').count();
+        }
+
+        output.push_str(rest);
+        output
+    }
+
+    /// Report `{% word}` statements with words which are not in dictionary, or first statement if
+    /// project has no dictionary
+    fn check_lookups(&self, file: &str, filepath: &str, diagnostics: &mut Diagnostics) {
+        let mut rest = file;
+        let mut line = 1;
+
+        while let Some(start) = rest.find("{%") {
+            let (before, statement) = rest.split_at(start);
+            line += before.matches('\n').count();
+
+            let end = match statement.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+            let word = statement[2..end].trim();
+            rest = &statement[end + 1..];
+
+            if !before.ends_with('\\') {
+                let level = self.config.dictionary.missing_words;
+                // Reported once for file, instead of for each word
+                let Some(dictionary) = self.dictionary else {
+                    diagnostics.push(Diagnostic::new(
+                        level,
+                        filepath,
+                        Some(line),
+                        format!(
+                            "cannot look up `{}` without dictionary in `{}`",
+                            word, self.config.files.dict
+                        ),
+                    ));
+                    return;
+                };
+                if dictionary.find(word).is_none() {
+                    diagnostics.push(Diagnostic::new(
+                        level,
+                        filepath,
+                        Some(line),
+                        format!("word `{}` is not in dictionary", word),
+                    ));
+                }
+            }
+
+            line += statement[..=end].matches('\n').count();
+        }
+    }
+
+    /// Report words of `{' text}` statements which break phonotactic rules, if `phonotactics.pages`
+    /// is set
+    ///
+    /// Statements with a language name, such as `{'eo text}`, are skipped
+    fn check_phonotactics(&self, file: &str, filepath: &str, diagnostics: &mut Diagnostics) {
+        let Some(phonotactics) = self.phonotactics else {
+            return;
+        };
+        if !self.config.phonotactics.pages {
+            return;
+        }
+        let mut rest = file;
+        let mut line = 1;
+
+        while let Some(start) = rest.find("{'") {
+            let (before, statement) = rest.split_at(start);
+            line += before.matches('\n').count();
+
+            let end = match statement.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+            let text = &statement[2..end];
+            rest = &statement[end + 1..];
+
+            if !before.ends_with('\\') && text.starts_with(char::is_whitespace) {
+                phonotactics.check_text(
+                    text,
+                    filepath,
+                    line,
+                    self.config.phonotactics.level,
+                    diagnostics,
+                );
+            }
+
+            line += statement[..=end].matches('\n').count();
+        }
+    }
+
+    /// Report test words of `{; rule ; words ;}` statements which break phonotactic rules, or which
+    /// are marked with `*` but do not
+    fn check_phoner_tests(&self, file: &str, filepath: &str, diagnostics: &mut Diagnostics) {
+        let mut rest = file;
+        let mut line = 1;
+
+        while let Some(start) = rest.find("{;") {
+            let (before, statement) = rest.split_at(start);
+            line += before.matches('\n').count();
+
+            let end = match statement.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+            let test = phonotactics::split_test(&statement[2..end]);
+            rest = &statement[end + 1..];
+
+            if let (Some((_, words)), false) = (test, before.ends_with('\\')) {
+                match self.phonotactics {
+                    Some(phonotactics) => phonotactics.check_test(
+                        &words,
+                        filepath,
+                        line,
+                        self.config.phonotactics.level,
+                        diagnostics,
+                    ),
+                    None => diagnostics.push(Diagnostic::warning(
+                        filepath,
+                        Some(line),
+                        format!(
+                            "cannot check words without phonotactic rules in `{}`",
+                            self.config.files.phoner
+                        ),
+                    )),
+                }
+            }
+
+            line += statement[..=end].matches('\n').count();
+        }
+    }
+
+    /// Report `{' text}` statements followed by `{/ ipa}`, where IPA does not match transcription
+    /// of text
+    fn check_transcriptions(&self, file: &str, filepath: &str, diagnostics: &mut Diagnostics) {
+        let Some(transcription) = self.transcription else {
+            return;
+        };
+        let mut rest = file;
+        let mut line = 1;
+
+        while let Some(start) = rest.find("{'") {
+            let (before, statement) = rest.split_at(start);
+            line += before.matches('\n').count();
+
+            let end = match statement.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+            let text = &statement[2..end];
+            rest = &statement[end + 1..];
+
+            // Only spaces between statements
+            let after = rest.trim_start_matches([' ', '\t']);
+            let ipa = after
+                .strip_prefix("{/")
+                .and_then(|ipa| ipa.find('}').map(|end| &ipa[..end]));
+            if let (Some(ipa), false) = (ipa, before.ends_with('\\')) {
+                if text.starts_with(char::is_whitespace) {
+                    transcription.check_ipa(
+                        text,
+                        ipa,
+                        filepath,
+                        line,
+                        self.config.transcription.level,
+                        diagnostics,
+                    );
+                }
+            }
+
+            line += statement[..=end].matches('\n').count();
+        }
+    }
+
+    /// Replace `{= text}` statements with `{/ ipa}` statement of transcription of text
+    fn expand_transcriptions(
+        &self,
+        file: &str,
+        filepath: &str,
+        diagnostics: &mut Diagnostics,
+    ) -> String {
+        expand_statements(file, "{=", |text, line| match self.transcription {
+            Some(transcription) => {
+                // Lines are kept, to preserve line numbers
+                let ipa = text
+                    .trim()
+                    .lines()
+                    .map(|text| transcription.apply_text(text))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                Some(format!("{{/ {}}}", ipa))
+            }
+            None => {
+                diagnostics.push(Diagnostic::error(
+                    filepath,
+                    Some(line),
+                    format!(
+                        "cannot transcribe without rules in `{}`",
+                        self.config.files.transcription
+                    ),
+                ));
+                None
+            }
+        })
+    }
+
+    /// Replacement of each `{: ipa}` statement, as `{/ ipa} {[ narrow]}` with allophony rules applied
+    /// to broad IPA, or table of allophony rules for `{:|}`
+    fn read_allophony(
+        &self,
+        file: &str,
+        filepath: &str,
+        diagnostics: &mut Diagnostics,
+    ) -> Vec<String> {
+        read_statements(file, "{:", |ipa, line| match self.allophony {
+            Some(allophony) if ipa.trim() == "|" => allophony.to_table(),
+            Some(allophony) => format!("{{/ {}}} {{[ {}}}", ipa.trim(), allophony.apply_ipa(ipa)),
+            None => {
+                diagnostics.push(Diagnostic::error(
+                    filepath,
+                    Some(line),
+                    format!(
+                        "cannot show allophones without rules in `{}`",
+                        self.config.files.allophony
+                    ),
+                ));
+                // Escaped, so statement is shown as text
+                format!("\\{{:{}}}", ipa)
+            }
+        })
+    }
+
+    /// Replacement of each `{>name words}` statement, as table of forms of each word after each
+    /// stage of sound changes, with build flags
+    ///
+    /// Name can be left out if project has one file of sound changes
+    fn read_derivations(
+        &self,
+        file: &str,
+        filepath: &str,
+        diagnostics: &mut Diagnostics,
+    ) -> Vec<String> {
+        read_statements(file, "{>", |statement, line| {
+            let (name, words) = statement
+                .split_once(char::is_whitespace)
+                .unwrap_or((statement, ""));
+            let sound_changes = match (name, self.sound_changes) {
+                ("", [sound_changes]) => Ok(sound_changes),
+                ("", _) => Err(format!(
+                    "statement must have name of sound changes in `{}`, such as `{{>name words}}`",
+                    self.config.files.sound_changes
+                )),
+                (name, _) => self
+                    .sound_changes
+                    .iter()
+                    .find(|sound_changes| sound_changes.name == name)
+                    .ok_or_else(|| {
+                        format!(
+                            "no sound changes `{}` in `{}`",
+                            name, self.config.files.sound_changes
+                        )
+                    }),
+            };
+
+            match sound_changes {
+                Ok(sound_changes) => {
+                    let mut table = sound_changes.derive_table(
+                        &words.split_whitespace().collect::<Vec<_>>(),
+                        &self.config.options.flags,
+                    );
+                    // Proto-forms are often marked with `*`, and optional forms separated with `~`
+                    for cell in table.rows.iter_mut().flatten() {
+                        *cell = escape_primatives(cell);
+                    }
+                    // Words are of proto-language, which `.llst` files call `word`
+                    table.to_table_with_header(&Column::Word, "'Proto")
+                }
+                Err(message) => {
+                    diagnostics.push(Diagnostic::error(filepath, Some(line), message));
+                    // Escaped, so statement is shown as text
+                    format!("\\{{>{}}}", statement)
+                }
+            }
+        })
+    }
+
+    /// Contents of other `.ling` file for each `{<path}` and `{<path#id}` statement, or empty text if
+    /// it cannot be included
+    ///
+    /// Path is relative to including file. With `#id`, only section under header with that id is included
+    fn read_includes(
+        &self,
+        file: &str,
+        filepath: &str,
+        stack: &mut Vec<String>,
+        diagnostics: &mut Diagnostics,
+    ) -> Vec<String> {
+        read_statements(file, "{<", |target, line| {
+            self.include(target.trim(), filepath, stack, diagnostics)
+                .unwrap_or_else(|message| {
+                    diagnostics.push(Diagnostic::error(filepath, Some(line), message));
+                    String::new()
+                })
+        })
+    }
+
+    /// Get processed contents of included file, or section of file
+    fn include(
+        &self,
+        target: &str,
+        filepath: &str,
+        stack: &mut Vec<String>,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<String, String> {
+        let (path, id) = match target.split_once('#') {
+            Some((path, id)) => (path, Some(id)),
+            None => (target, None),
+        };
+
+        let included_path = join_relative(filepath, path)
+            .ok_or_else(|| format!("cannot include `{}` from outside source directory", path))?;
+
+        if stack.contains(&included_path) {
+            return Err(format!(
+                "include cycle: {} -> {}",
+                stack.join(" -> "),
+                included_path
+            ));
+        }
+
+        let file = fs::read_to_string(Path::new(&self.config.files.source).join(&included_path))
+            .map_err(|err| format!("could not include `{}`: {}", included_path, err))?;
+        let (_, file) = split_front_matter(&file)
+            .map_err(|err| format!("could not include `{}`: {}", included_path, err))?;
+
+        // Word list is included as table
+        if included_path.ends_with(".llst") {
+            if id.is_some() {
+                return Err(format!(
+                    "cannot include section of word list `{}`",
+                    included_path
+                ));
+            }
+            return Ok(WordList::parse(&file, &included_path, diagnostics).to_table());
+        }
+
+        stack.push(included_path.clone());
+        let file = self.process_nested(&file, &included_path, stack, diagnostics);
+        stack.pop();
+
+        match id {
+            Some(id) => select_section(&file, id)
+                .ok_or_else(|| format!("no header with id `{}` in `{}`", id, included_path)),
+            None => Ok(file.trim_matches('\n').to_string()),
+        }
+    }
+}
+
+/// Replacement of each statement starting with `start`, from `read`, which is given contents of
+/// statement and its line number
+///
+/// Statements escaped with `\` are skipped
+fn read_statements(
+    file: &str,
+    start: &str,
+    mut read: impl FnMut(&str, usize) -> String,
+) -> Vec<String> {
+    let mut replacements = Vec::new();
+    expand_statements(file, start, |statement, line| {
+        replacements.push(read(statement, line));
+        None
+    });
+    replacements
+}
+
+/// Replace statements starting with `start` with replacements from [`read_statements`], in order
+fn replace_statements(file: &str, start: &str, replacements: Vec<String>) -> String {
+    let mut replacements = replacements.into_iter();
+    expand_statements(file, start, |_, _| replacements.next())
+}
+
+/// Replace each statement starting with `start` with result of `expand`, which is given contents
+/// of statement and its line number, or keep statement if `expand` returns `None`
+///
+/// Statements escaped with `\` are kept
+fn expand_statements(
+    file: &str,
+    start: &str,
+    mut expand: impl FnMut(&str, usize) -> Option<String>,
+) -> String {
+    let mut output = String::new();
+    let mut rest = file;
+    let mut line = 1;
+
+    while let Some(index) = rest.find(start) {
+        let (before, statement) = rest.split_at(index);
+        output.push_str(before);
+        line += before.matches('\n').count();
+
+        let end = match statement.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        let statement_text = &statement[..=end];
+        rest = &statement[end + 1..];
+
+        let expanded = match before.ends_with('\\') {
+            true => None,
+            false => expand(&statement[start.len()..end], line),
+        };
+        output.push_str(expanded.as_deref().unwrap_or(statement_text));
+
+        line += statement_text.matches('\n').count();
+    }
+
+    output.push_str(rest);
+    output
+}
+
+/// Get header with id, and all lines until next header of same or higher level
+///
+/// Ids of headers are counted like ids of page, so `a-2` is second header with id `a`
+fn select_section(file: &str, id: &str) -> Option<String> {
+    let mut section_level = None;
+    let mut lines = Vec::new();
+    let mut id_counts = HashMap::new();
+
+    for line in file.lines() {
+        let header = header_level(line);
+
+        match (section_level, header) {
+            (None, Some((level, text))) if header_id(text, &mut id_counts) == id => {
+                section_level = Some(level);
+                lines.push(line);
+            }
+            (Some(section_level), Some((level, _))) if level <= section_level => break,
+            (Some(_), _) => lines.push(line),
+            _ => (),
+        }
+    }
+
+    section_level.map(|_| lines.join("\n").trim_end().to_string())
+}
+
+/// Get level and text of line, if line is a header
+fn header_level(line: &str) -> Option<(usize, &str)> {
+    let (token, text) = line.split_once(' ')?;
+    if !token.is_empty() && token.chars().all(|ch| ch == '#') {
+        Some((token.len(), text.trim()))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_conditionals_works() {
+        let config =
+            Config::from("[package]\nname = \"abc\"\ncode = \"ab\"\n[options]\nflags = [\"a\"]")
+                .unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let preprocessor =
+            Preprocessor::new(&config, None, None, None, None, &[], &mut diagnostics);
+
+        assert_eq!(
+            preprocessor.expand_conditionals(
+                "x {?a yes {?a {'nested}}} {?!a no}\n{?b\nhidden\n}\n\\{?b z}",
+                "test.ling",
+                1,
+                &mut diagnostics
+            ),
+            "x yes {'nested} \n\n\n\n\\{?b z}"
+        );
+        assert!(diagnostics.report().is_ok());
+    }
+
+    #[test]
+    fn check_lookups_works() {
+        let config = Config::from("[package]\nname = \"abc\"\ncode = \"ab\"").unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let dictionary =
+            Dictionary::parse("kata\n  - cat", "dict.ldct", "en", &[], &mut diagnostics);
+        let preprocessor = Preprocessor::new(
+            &config,
+            Some(&dictionary),
+            None,
+            None,
+            None,
+            &[],
+            &mut diagnostics,
+        );
+
+        preprocessor.check_lookups(
+            "{% kata} {% Kata}\n\\{% pita}\n{% pita}",
+            "test.ling",
+            &mut diagnostics,
+        );
+        let messages = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec!["error: test.ling:3: word `pita` is not in dictionary"]
+        );
+
+        let preprocessor =
+            Preprocessor::new(&config, None, None, None, None, &[], &mut diagnostics);
+        preprocessor.check_lookups("\\{% a}\n{% kata} {% pita}", "test.ling", &mut diagnostics);
+        assert_eq!(
+            diagnostics.iter().last().unwrap().to_string(),
+            "error: test.ling:2: cannot look up `kata` without dictionary in `dict.ldct`"
+        );
+        assert_eq!(diagnostics.count(), 2);
+    }
+
+    #[test]
+    fn lines_after_macros_are_lines_of_file() {
+        let config = Config::from(
+            "[package]\nname = \"abc\"\ncode = \"ab\"\n[macros.two]\nbody = \"a\\nb\\n{% kata}\"",
+        )
+        .unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let dictionary =
+            Dictionary::parse("kata\n  - cat", "dict.ldct", "en", &[], &mut diagnostics);
+        let preprocessor = Preprocessor::new(
+            &config,
+            Some(&dictionary),
+            None,
+            None,
+            None,
+            &[],
+            &mut diagnostics,
+        );
+
+        preprocessor.process("{!two}\nx\n{% nope}\n{: a}", "index.ling", &mut diagnostics);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.line)
+                .collect::<Vec<_>>(),
+            vec![Some(3), Some(4)]
+        );
+    }
+
+    #[test]
+    fn check_phonotactics_works() {
+        let config = Config::from(
+            "[package]\nname = \"abc\"\ncode = \"ab\"\n[phonotactics]\nlevel = \"warning\"\npages = true",
+        )
+        .unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let phonotactics = Phonotactics::parse("! aa", "phoner", &mut diagnostics);
+        let preprocessor = Preprocessor::new(
+            &config,
+            None,
+            Some(&phonotactics),
+            None,
+            None,
+            &[],
+            &mut diagnostics,
+        );
+
+        preprocessor.check_phonotactics(
+            "{' kata} {'en baa}\n\\{' baa}\n{' kata,\n  baa}",
+            "test.ling",
+            &mut diagnostics,
+        );
+        let messages = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec!["word `baa` matches forbidden phonotactic rule `! aa`"]
+        );
+        assert_eq!(diagnostics.iter().next().unwrap().line, Some(4));
+    }
+
+    #[test]
+    fn check_phoner_tests_works() {
+        let config = Config::from("[package]\nname = \"abc\"\ncode = \"ab\"").unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let phonotactics = Phonotactics::parse("! aa", "phoner", &mut diagnostics);
+        let preprocessor = Preprocessor::new(
+            &config,
+            None,
+            Some(&phonotactics),
+            None,
+            None,
+            &[],
+            &mut diagnostics,
+        );
+
+        preprocessor.check_phoner_tests(
+            "{; ! aa ;}\n{; ! aa ; kata *kaat baa *kata ;}\n\\{; ; baa}",
+            "test.ling",
+            &mut diagnostics,
+        );
+        let messages = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "word `baa` matches forbidden phonotactic rule `! aa`",
+                "word `kata` is marked with `*`, but follows phonotactic rules",
+            ]
+        );
+    }
+
+    #[test]
+    fn transcriptions_work() {
+        let config = Config::from("[package]\nname = \"abc\"\ncode = \"ab\"").unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let transcription = Rewrite::parse("ŝ > ʃ\nng > ŋ", "transcription", &[], &mut diagnostics);
+        let preprocessor = Preprocessor::new(
+            &config,
+            None,
+            None,
+            Some(&transcription),
+            None,
+            &[],
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            preprocessor.expand_transcriptions(
+                "{= ŝalo, ngulo} \\{= ŝa}\n{= ŝa\nŝa}",
+                "test.ling",
+                &mut diagnostics
+            ),
+            "{/ ʃalo, ŋulo} \\{= ŝa}\n{/ ʃa\nʃa}"
+        );
+        preprocessor.check_transcriptions(
+            "{' ŝalo} {/ˈʃa.lo/}\n{' ŝalo} {/ˈsa.lo/}\n{'eo ŝalo} {/ˈsa.lo/}",
+            "test.ling",
+            &mut diagnostics,
+        );
+        let messages = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "warning: test.ling:2: IPA `/ˈsa.lo/` of `ŝalo` does not match transcription `/ʃalo/`\n  \
+                note: applied rules `ŝ > ʃ` (transcription:1)"
+            ]
+        );
+
+        let preprocessor =
+            Preprocessor::new(&config, None, None, None, None, &[], &mut diagnostics);
+        preprocessor.expand_transcriptions("\n{= ŝa}", "test.ling", &mut diagnostics);
+        assert_eq!(
+            diagnostics.iter().last().unwrap().to_string(),
+            "error: test.ling:2: cannot transcribe without rules in `transcription`"
+        );
+    }
+
+    #[test]
+    fn expand_allophony_works() {
+        let config = Config::from("[package]\nname = \"abc\"\ncode = \"ab\"").unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let allophony = Rewrite::parse("d > ð / a _ a", "allophony", &[], &mut diagnostics);
+        let preprocessor = Preprocessor::new(
+            &config,
+            None,
+            None,
+            None,
+            Some(&allophony),
+            &[],
+            &mut diagnostics,
+        );
+
+        let file = "{: ˈka.da} \\{: da}\n{:|}";
+        assert_eq!(
+            replace_statements(
+                file,
+                "{:",
+                preprocessor.read_allophony(file, "test.ling", &mut diagnostics)
+            ),
+            "{/ ˈka.da} {[ ˈka.ða} \\{: da}\n{|\n/Phoneme |[Allophone |;Environment\nd | ð | a _ a\n}"
+        );
+
+        let preprocessor =
+            Preprocessor::new(&config, None, None, None, None, &[], &mut diagnostics);
+        assert_eq!(
+            preprocessor.read_allophony("\n{: da}", "test.ling", &mut diagnostics),
+            vec!["\\{: da}"]
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["error: test.ling:2: cannot show allophones without rules in `allophony`"]
+        );
+    }
+
+    #[test]
+    fn expand_derivations_works() {
+        let config = Config::from("[package]\nname = \"abc\"\ncode = \"ab\"").unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let sound_changes = [
+            SoundChanges::parse(
+                "@stage Old\np > f",
+                "sound-changes/old",
+                "old",
+                &mut diagnostics,
+            ),
+            SoundChanges::parse("? a > e", "sound-changes/new", "new", &mut diagnostics),
+        ];
+        let preprocessor = Preprocessor::new(
+            &config,
+            None,
+            None,
+            None,
+            None,
+            &sound_changes,
+            &mut diagnostics,
+        );
+
+        let file = "{>old *pata}\n{>new pata}\n{> pata}\n{>other pata}";
+        assert_eq!(
+            replace_statements(
+                file,
+                "{>",
+                preprocessor.read_derivations(file, "test.ling", &mut diagnostics)
+            ),
+            "{|\n'Proto |'Old\n\\*pata | fata\n}\n{|\n'Proto |'new\npata | pata \\~ pete\n}\n\
+            \\{> pata}\n\\{>other pata}"
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "error: test.ling:3: statement must have name of sound changes in `sound-changes`, \
+                such as `{>name words}`",
+                "error: test.ling:4: no sound changes `other` in `sound-changes`",
+            ]
+        );
+    }
+
+    #[test]
+    fn includes_work() {
+        let dir =
+            std::env::temp_dir().join(format!("lingdocs-include-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("dir")).unwrap();
+        for (path, file) in [
+            ("a.ling", "{<b.ling}"),
+            ("b.ling", "b\n{<a.ling}"),
+            ("e.ling", "e"),
+            ("dir/c.ling", "# Part One\nc\n# Other\nx\n"),
+            ("dir/d.ling", "{<c.ling#part-one}\n{<../e.ling}"),
+        ] {
+            fs::write(dir.join(path), file).unwrap();
+        }
+
+        let mut config = Config::from("[package]\nname = \"abc\"\ncode = \"ab\"").unwrap();
+        config.files.source = dir.to_string_lossy().to_string();
+        let mut diagnostics = Diagnostics::default();
+        let preprocessor =
+            Preprocessor::new(&config, None, None, None, None, &[], &mut diagnostics);
+
+        assert_eq!(
+            preprocessor.process("{<d.ling}", "dir/index.ling", &mut diagnostics),
+            "# Part One\nc\ne"
+        );
+        assert!(diagnostics.report().is_ok());
+
+        assert_eq!(
+            preprocessor.process("{<a.ling}", "index.ling", &mut diagnostics),
+            "b"
+        );
+        preprocessor.process(
+            "{<missing.ling}\n{<../a.ling}\n{<dir/c.ling#none}",
+            "index.ling",
+            &mut diagnostics,
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        let messages = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages[0],
+            "error: b.ling:2: include cycle: index.ling -> a.ling -> b.ling -> a.ling"
+        );
+        assert!(messages[1].starts_with("error: index.ling:1: could not include `missing.ling`: "));
+        assert_eq!(
+            messages[2..],
+            [
+                "error: index.ling:2: cannot include `../a.ling` from outside source directory",
+                "error: index.ling:3: no header with id `none` in `dir/c.ling`",
+            ]
+        );
+    }
+
+    #[test]
+    fn statements_after_includes_have_lines() {
+        let dir = std::env::temp_dir().join(format!(
+            "lingdocs-include-lines-test-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("other.ling"), "a\n{: da}\nb\n{>x da}\n").unwrap();
+
+        let mut config = Config::from("[package]\nname = \"abc\"\ncode = \"ab\"").unwrap();
+        config.files.source = dir.to_string_lossy().to_string();
+        let mut diagnostics = Diagnostics::default();
+        let preprocessor =
+            Preprocessor::new(&config, None, None, None, None, &[], &mut diagnostics);
+
+        assert_eq!(
+            preprocessor.process(
+                "{<other.ling}\n\n{: kata}\n{>x kata}",
+                "c.ling",
+                &mut diagnostics
+            ),
+            "a\n\\{: da}\nb\n\\{>x da}\n\n\\{: kata}\n\\{>x kata}"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| format!("{}:{}", diagnostic.file, diagnostic.line.unwrap()))
+                .collect::<Vec<_>>(),
+            vec!["other.ling:2", "other.ling:4", "c.ling:3", "c.ling:4"]
+        );
+    }
+
+    #[test]
+    fn select_section_works() {
+        let file = "# a\nabc\n## b\ndef\n### c\nghi\n## d\njkl";
+
+        assert_eq!(
+            select_section(file, "b").as_deref(),
+            Some("## b\ndef\n### c\nghi")
+        );
+        assert_eq!(select_section(file, "d").as_deref(), Some("## d\njkl"));
+        assert_eq!(
+            select_section("# A\nx\n# A\ny\n## A\nz", "a-2").as_deref(),
+            Some("# A\ny\n## A\nz")
+        );
+        assert_eq!(
+            select_section("# A\nx\n# A\ny\n## A\nz", "a-3").as_deref(),
+            Some("## A\nz")
+        );
+        assert_eq!(select_section(file, "e"), None);
+    }
+}
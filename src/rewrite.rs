@@ -0,0 +1,716 @@
+use std::{fs, path::Path};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics, Level},
+    ipa,
+    phonotactics::words,
+    Config,
+};
+
+/// Ordered rewrite rules, such as for transcription or allophony, parsed from rules file
+///
+/// ```txt
+/// # Classes, with letters separated by spaces
+/// V = a e i o u
+/// Stop = p t k
+/// Fric = f s x
+///
+/// # Rules, applied in order, as `from > to` or `from > to / before _ after`
+/// ch > tʃ
+/// <Stop> > <Fric> / <V> _ <V>
+/// e > ə / _ #
+/// h > / # _
+/// ```
+///
+/// Spaces are ignored, `<C>` is any letter of class `C`, and `#` is start or end of word.
+/// Classes can be features, combined as `<Stop&Voiced>` or `<Stop&!Voiced>`.
+/// Stress marks and syllable breaks are skipped in environments, unless written in them.
+/// A class in `to` is replaced with the letter of the same position as the letter of the class in
+/// `from`
+#[derive(Debug)]
+pub struct Rewrite {
+    /// Path of rules file, relative to source directory
+    pub filepath: String,
+    pub rules: Vec<Rule>,
+    /// Letters of classes and other multigraphs, longest first
+    graphemes: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    /// Rule as written
+    pub text: String,
+    pub line: usize,
+    /// Comment before rule
+    pub description: Option<String>,
+    from: Vec<Item>,
+    to: Vec<Item>,
+    before: Vec<Item>,
+    after: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    Letter(String),
+    /// Letters of class
+    Class(Vec<String>),
+    /// Start or end of word
+    Boundary,
+}
+
+impl Rewrite {
+    /// Parse rules file of project, if it exists
+    ///
+    /// `multigraphs` are not split into characters, such as letters of `[orthography]`
+    pub fn load(
+        config: &Config,
+        filepath: &str,
+        multigraphs: &[String],
+        diagnostics: &mut Diagnostics,
+    ) -> Option<Self> {
+        let path = Path::new(&config.files.source).join(filepath);
+        if !path.is_file() {
+            return None;
+        }
+
+        let file = fs::read_to_string(path).expect("Could not read rules file");
+        Some(Rewrite::parse(&file, filepath, multigraphs, diagnostics))
+    }
+
+    /// Parse rules file, with multigraphs which are not split, as well as letters of classes
+    pub fn parse(
+        file: &str,
+        filepath: &str,
+        multigraphs: &[String],
+        diagnostics: &mut Diagnostics,
+    ) -> Self {
+        let mut classes = Vec::<(String, Vec<String>)>::new();
+        let mut graphemes = multigraphs
+            .iter()
+            .map(|letter| letter.nfc().collect::<String>())
+            .collect::<Vec<_>>();
+        // Classes are parsed first, so letters of later classes are not split in earlier rules
+        let lines = file
+            .lines()
+            .enumerate()
+            .map(|(line_num, line)| (line_num + 1, line.trim()))
+            .collect::<Vec<_>>();
+
+        for (line, text) in &lines {
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            match split_class(text) {
+                Some((name, list)) => {
+                    // Letters can include earlier classes, such as `L = <C> <V>`
+                    let mut letters = Vec::<String>::new();
+                    for letter in list.split_whitespace() {
+                        match letter.strip_prefix('<').and_then(|c| c.strip_suffix('>')) {
+                            Some(other) => match class_letters(other, &classes) {
+                                Ok(other) => letters.extend(other),
+                                Err(message) => diagnostics.push(Diagnostic::error(
+                                    filepath,
+                                    Some(*line),
+                                    message,
+                                )),
+                            },
+                            None => letters.push(letter.nfc().collect()),
+                        }
+                    }
+                    if letters.is_empty() {
+                        diagnostics.push(Diagnostic::error(
+                            filepath,
+                            Some(*line),
+                            "class has no letters",
+                        ));
+                    }
+                    graphemes.extend(letters.iter().cloned());
+                    classes.push((name.to_string(), letters));
+                }
+                None if is_rule(text) => (),
+                None => diagnostics.push(Diagnostic::error(
+                    filepath,
+                    Some(*line),
+                    "expected class such as `V = a i u`, or rule such as `a > b / _ c`",
+                )),
+            }
+        }
+        graphemes.sort_by_key(|letter| usize::MAX - letter.chars().count());
+        graphemes.dedup();
+
+        let mut rewrite = Rewrite {
+            filepath: filepath.to_string(),
+            rules: Vec::new(),
+            graphemes,
+        };
+        let mut description = None;
+        for (line, text) in lines {
+            if let Some(comment) = text.strip_prefix('#') {
+                description = Some(comment.trim().to_string());
+                continue;
+            }
+            if is_rule(text) {
+                match rewrite.parse_rule(text, line, &classes) {
+                    Ok(rule) => rewrite.rules.push(Rule {
+                        description: description.take(),
+                        ..rule
+                    }),
+                    Err(message) => {
+                        diagnostics.push(Diagnostic::error(filepath, Some(line), message))
+                    }
+                }
+            }
+            description = None;
+        }
+        rewrite
+    }
+
+    /// Parse `from > to / before _ after`
+    fn parse_rule(
+        &self,
+        text: &str,
+        line: usize,
+        classes: &[(String, Vec<String>)],
+    ) -> Result<Rule, String> {
+        let (from, to, environment) =
+            split_rule(text).ok_or_else(|| "expected rule such as `a > b / _ c`".to_string())?;
+        let (before, after) = environment
+            .unwrap_or("_")
+            .split_once('_')
+            .ok_or_else(|| "environment must have `_`, such as `/ a _ b`".to_string())?;
+
+        let from = self.parse_items(from, classes)?;
+        let to = self.parse_items(to.trim().trim_start_matches('∅'), classes)?;
+        if from.is_empty() {
+            return Err("rule must replace at least one letter".to_string());
+        }
+        if from.iter().chain(&to).any(|item| *item == Item::Boundary) {
+            return Err("`#` can only be in environment".to_string());
+        }
+
+        // Each class of `to` takes letter from class in same position of `from`
+        let from_classes = from
+            .iter()
+            .filter_map(|item| match item {
+                Item::Class(letters) => Some(letters),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let to_classes = to.iter().filter_map(|item| match item {
+            Item::Class(letters) => Some(letters),
+            _ => None,
+        });
+        for (i, letters) in to_classes.enumerate() {
+            match from_classes.get(i) {
+                Some(from_letters) if from_letters.len() == letters.len() => (),
+                Some(_) => {
+                    return Err(format!(
+                        "class {} of `to` has a different number of letters to class of `from`",
+                        i + 1
+                    ))
+                }
+                None => return Err(format!("class {} of `to` is not in `from`", i + 1)),
+            }
+        }
+
+        Ok(Rule {
+            text: text.to_string(),
+            line,
+            description: None,
+            from,
+            to,
+            before: self.parse_items(before, classes)?,
+            after: self.parse_items(after, classes)?,
+        })
+    }
+
+    /// Letters, classes and boundaries of part of rule
+    fn parse_items(
+        &self,
+        text: &str,
+        classes: &[(String, Vec<String>)],
+    ) -> Result<Vec<Item>, String> {
+        let text = text
+            .split_whitespace()
+            .collect::<String>()
+            .nfc()
+            .collect::<String>();
+        let mut items = Vec::new();
+        let mut rest = text.as_str();
+
+        while !rest.is_empty() {
+            if let Some(class) = rest.strip_prefix('<') {
+                let (name, after) = class
+                    .split_once('>')
+                    .ok_or_else(|| "class is never closed with `>`".to_string())?;
+                items.push(Item::Class(class_letters(name, classes)?));
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('#') {
+                items.push(Item::Boundary);
+                rest = after;
+            } else {
+                let letter = self.next_letter(rest);
+                rest = &rest[letter.len()..];
+                items.push(Item::Letter(letter.to_string()));
+            }
+        }
+        Ok(items)
+    }
+
+    /// Longest multigraph at start of text, or character with its diacritics
+    fn next_letter<'a>(&self, text: &'a str) -> &'a str {
+        if let Some(grapheme) = self
+            .graphemes
+            .iter()
+            .find(|grapheme| text.starts_with(grapheme.as_str()))
+        {
+            return &text[..grapheme.len()];
+        }
+        let mut chars = text.char_indices();
+        chars.next();
+        let end = chars
+            .find(|(_, ch)| !ipa::is_modifier(*ch))
+            .map_or(text.len(), |(i, _)| i);
+        &text[..end]
+    }
+
+    /// Letters of word, in lowercase
+    pub fn letters(&self, word: &str) -> Vec<String> {
+        let word = word.nfc().collect::<String>().to_lowercase();
+        let mut letters = Vec::new();
+        let mut rest = word.as_str();
+        while !rest.is_empty() {
+            let letter = self.next_letter(rest);
+            rest = &rest[letter.len()..];
+            letters.push(letter.to_string());
+        }
+        letters
+    }
+
+    /// Word with each rule applied in order
+    pub fn apply(&self, word: &str) -> String {
+        self.rules
+            .iter()
+            .fold(self.letters(word), |letters, rule| rule.apply(&letters))
+            .concat()
+    }
+
+    /// Each rule which changes word, with word after it
+    pub fn trace(&self, word: &str) -> Vec<(&Rule, String)> {
+        let mut letters = self.letters(word);
+        let mut trace = Vec::new();
+        for rule in &self.rules {
+            let next = rule.apply(&letters);
+            if next != letters {
+                trace.push((rule, next.concat()));
+            }
+            letters = next;
+        }
+        trace
+    }
+
+    /// Text with rules applied, if it has different phonemes to `ipa`, ignoring stress, syllable
+    /// breaks and punctuation
+    pub fn mismatch(&self, text: &str, ipa: &str) -> Option<String> {
+        let transcription = words(text)
+            .map(|word| self.apply(word))
+            .collect::<Vec<_>>()
+            .join(" ");
+        (ipa::phonemes(&transcription) != ipa::phonemes(ipa)).then_some(transcription)
+    }
+
+    /// Report IPA which does not match transcription of text
+    pub fn check_ipa(
+        &self,
+        text: &str,
+        ipa: &str,
+        filepath: &str,
+        line: usize,
+        level: Level,
+        diagnostics: &mut Diagnostics,
+    ) {
+        if let Some(transcription) = self.mismatch(text, ipa) {
+            diagnostics.push(
+                Diagnostic::new(
+                    level,
+                    filepath,
+                    Some(line),
+                    format!(
+                        "IPA `/{}/` of `{}` does not match transcription `/{}/`",
+                        ipa.trim().trim_matches('/').trim(),
+                        text.trim(),
+                        transcription
+                    ),
+                )
+                .with_note(self.rules_note(text)),
+            );
+        }
+    }
+
+    /// Rules applied to words of text, with their locations
+    fn rules_note(&self, text: &str) -> String {
+        let rules = words(text)
+            .flat_map(|word| self.trace(word))
+            .map(|(rule, _)| format!("`{}` ({}:{})", rule.text, self.filepath, rule.line))
+            .collect::<Vec<_>>();
+        if rules.is_empty() {
+            format!("no rules of {} apply", self.filepath)
+        } else {
+            format!("applied rules {}", rules.join(", "))
+        }
+    }
+
+    /// IPA transcription with rules applied to each word, without `/` or `[]` around it
+    ///
+    /// Stress marks and syllable breaks are kept, and are skipped when matching environments
+    pub fn apply_ipa(&self, ipa: &str) -> String {
+        ipa.trim()
+            .trim_start_matches(['/', '['])
+            .trim_end_matches(['/', ']'])
+            .split_whitespace()
+            .map(|word| self.apply(word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Rules as table in `.ling` file, with `from` as broad IPA and `to` as narrow IPA
+    pub fn to_table(&self) -> String {
+        let has_notes = self.rules.iter().any(|rule| rule.description.is_some());
+        let mut lines = vec![
+            "{|".to_string(),
+            // Format character must be first character of header cell
+            if has_notes {
+                "/Phoneme |[Allophone |;Environment |Notes"
+            } else {
+                "/Phoneme |[Allophone |;Environment"
+            }
+            .to_string(),
+        ];
+        for rule in &self.rules {
+            let (from, to, environment) = rule.parts();
+            let mut cells = vec![
+                from,
+                if to.is_empty() { "∅" } else { to },
+                environment.unwrap_or_default(),
+            ];
+            if has_notes {
+                cells.push(rule.description.as_deref().unwrap_or_default());
+            }
+            lines.push(cells.join(" | "));
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    /// Each word of text with rules applied, keeping spaces and punctuation around words
+    pub fn apply_text(&self, text: &str) -> String {
+        let is_punctuation = |ch: char| !ch.is_alphanumeric();
+        text.split_whitespace()
+            .map(|word| {
+                let start = word.len() - word.trim_start_matches(is_punctuation).len();
+                let end = word.trim_end_matches(is_punctuation).len().max(start);
+                format!(
+                    "{}{}{}",
+                    &word[..start],
+                    self.apply(&word[start..end]),
+                    &word[end..]
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl Rule {
+    /// `from`, `to` and environment of rule as written
+    pub fn parts(&self) -> (&str, &str, Option<&str>) {
+        split_rule(&self.text).expect("Rule was parsed")
+    }
+
+    /// Letters with rule applied at each position where it matches, from left to right, without
+    /// overlapping
+    ///
+    /// Environment is matched with letters before rule is applied
+    pub fn apply(&self, letters: &[String]) -> Vec<String> {
+        let mut output = Vec::new();
+        let mut i = 0;
+        while i < letters.len() {
+            match self.match_at(letters, i) {
+                Some((end, indexes)) => {
+                    output.extend(self.replacement(&indexes));
+                    i = end;
+                }
+                None => {
+                    output.push(letters[i].clone());
+                    i += 1;
+                }
+            }
+        }
+        output
+    }
+
+    /// End of match of `from` at position, with index of letter of each class, if environment matches
+    fn match_at(&self, letters: &[String], start: usize) -> Option<(usize, Vec<usize>)> {
+        let mut indexes = Vec::new();
+        let end = match_forward(&self.from, letters, start, &mut indexes, false)?;
+        match_forward(&self.after, letters, end, &mut Vec::new(), true)?;
+
+        // Match `before` backwards from start
+        let mut position = start;
+        for item in self.before.iter().rev() {
+            if !item.is_mark() {
+                while position > 0 && is_mark(&letters[position - 1]) {
+                    position -= 1;
+                }
+            }
+            match item {
+                Item::Boundary if position == 0 => (),
+                Item::Boundary => return None,
+                _ if position == 0 => return None,
+                _ => {
+                    item.index(&letters[position - 1])?;
+                    position -= 1;
+                }
+            }
+        }
+
+        Some((end, indexes))
+    }
+
+    /// Letters of `to`, with letters of classes at `indexes`
+    fn replacement(&self, indexes: &[usize]) -> Vec<String> {
+        let mut indexes = indexes.iter();
+        self.to
+            .iter()
+            .filter_map(|item| match item {
+                Item::Letter(letter) => Some(letter.clone()),
+                Item::Class(letters) => indexes.next().map(|&i| letters[i].clone()),
+                Item::Boundary => None,
+            })
+            .collect()
+    }
+}
+
+impl Item {
+    fn is_mark(&self) -> bool {
+        matches!(self, Item::Letter(letter) if is_mark(letter))
+    }
+
+    /// Index of letter in class, or `0` if item is same letter
+    fn index(&self, letter: &str) -> Option<usize> {
+        match self {
+            Item::Letter(other) => (other == letter).then_some(0),
+            Item::Class(letters) => letters.iter().position(|other| other == letter),
+            Item::Boundary => None,
+        }
+    }
+}
+
+/// End of match of items from position, adding index of letter of each class to `indexes`
+///
+/// With `skip_marks`, stress marks and syllable breaks are skipped, unless item is one
+fn match_forward(
+    items: &[Item],
+    letters: &[String],
+    start: usize,
+    indexes: &mut Vec<usize>,
+    skip_marks: bool,
+) -> Option<usize> {
+    let mut position = start;
+    for item in items {
+        if skip_marks && !item.is_mark() {
+            while letters.get(position).is_some_and(|letter| is_mark(letter)) {
+                position += 1;
+            }
+        }
+        match item {
+            Item::Boundary if position == letters.len() => (),
+            Item::Boundary => return None,
+            _ => {
+                let index = item.index(letters.get(position)?)?;
+                if let Item::Class(_) = item {
+                    indexes.push(index);
+                }
+                position += 1;
+            }
+        }
+    }
+    Some(position)
+}
+
+/// `from`, `to` and environment of `from > to / before _ after`, split at first `>` which does
+/// not close a class
+fn split_rule(text: &str) -> Option<(&str, &str, Option<&str>)> {
+    let mut depth = 0;
+    let arrow = text.find(|ch| {
+        match ch {
+            '<' => depth += 1,
+            '>' if depth > 0 => depth -= 1,
+            '>' => return true,
+            _ => (),
+        }
+        false
+    })?;
+    let (from, rest) = (&text[..arrow], &text[arrow + 1..]);
+    let (to, environment) = match rest.split_once('/') {
+        Some((to, environment)) => (to, Some(environment.trim())),
+        None => (rest, None),
+    };
+    Some((from.trim(), to.trim(), environment))
+}
+
+/// Letters of class, such as `<V>`, or letters of first class which are also in other classes,
+/// or not in classes with `!`, such as `<Stop&!Voiced>`
+fn class_letters(name: &str, classes: &[(String, Vec<String>)]) -> Result<Vec<String>, String> {
+    let find = |name: &str| {
+        classes
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, letters)| letters)
+            .ok_or_else(|| format!("unknown class `<{}>`", name))
+    };
+    let mut parts = name.split('&');
+    let mut letters = find(parts.next().unwrap_or_default())?.clone();
+    for part in parts {
+        let (negate, part) = match part.strip_prefix('!') {
+            Some(part) => (true, part),
+            None => (false, part),
+        };
+        let other = find(part)?;
+        letters.retain(|letter| other.contains(letter) != negate);
+    }
+
+    if letters.is_empty() {
+        return Err(format!("class `<{}>` has no letters", name));
+    }
+    Ok(letters)
+}
+
+/// Stress mark or syllable break of IPA
+fn is_mark(letter: &str) -> bool {
+    matches!(letter, "ˈ" | "ˌ" | ".")
+}
+
+fn is_class_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+}
+
+/// Name and letters of class line, such as `V = a i u`
+fn split_class(text: &str) -> Option<(&str, &str)> {
+    let (name, letters) = text.split_once('=')?;
+    is_class_name(name.trim()).then(|| (name.trim(), letters))
+}
+
+/// Whether line is a rule, such as `a > b / _ c`, and not a class or comment
+pub fn is_rule(text: &str) -> bool {
+    !text.starts_with('#') && text.contains('>') && split_class(text).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = "\
+        # Classes\n\
+        V = a e i o u\n\
+        Stop = p t k\n\
+        Fric = f s x\n\
+        \n\
+        # Rules\n\
+        ch > tʃ\n\
+        ng > ŋ\n\
+        <Stop> > <Fric> / <V> _ <V>\n\
+        e > ə / _ #\n\
+        h > ∅ / # _\n";
+
+    #[test]
+    fn apply_works() {
+        let mut diagnostics = Diagnostics::default();
+        let rewrite = Rewrite::parse(RULES, "rules", &["ch".to_string()], &mut diagnostics);
+        assert!(diagnostics.report().is_ok());
+
+        assert_eq!(rewrite.letters("Chata"), vec!["ch", "a", "t", "a"]);
+        assert_eq!(rewrite.apply("chata"), "tʃasa");
+        assert_eq!(rewrite.apply("hapeke"), "afexə");
+        assert_eq!(rewrite.apply("ngeh"), "ŋeh");
+        assert_eq!(rewrite.apply("atta"), "atta");
+        assert_eq!(rewrite.apply_text("\"Hapa, chato!\""), "\"afa, tʃaso!\"");
+
+        assert_eq!(rewrite.mismatch("Hapa, chato!", "/ˈa.fa ˈtʃa.so/"), None);
+        assert_eq!(rewrite.mismatch("hapa", "/ˈa.pa/").as_deref(), Some("afa"));
+    }
+
+    #[test]
+    fn allophony_works() {
+        let mut diagnostics = Diagnostics::default();
+        let allophony = Rewrite::parse(
+            "V = a e i o u\n# Between vowels\nd > ð / <V> _ <V>\nt > tʰ / # _\na > ɐ / _ .",
+            "allophony",
+            &[],
+            &mut diagnostics,
+        );
+        assert!(diagnostics.report().is_ok());
+
+        assert_eq!(allophony.apply_ipa("/kaˈti.do/"), "kaˈti.ðo");
+        assert_eq!(allophony.apply_ipa("[ˈta.du ˈtu]"), "ˈtʰɐ.ðu ˈtʰu");
+        assert_eq!(allophony.apply_ipa("ˈda"), "ˈda");
+        assert_eq!(
+            allophony.to_table(),
+            "{|\n/Phoneme |[Allophone |;Environment |Notes\n\
+            d | ð | <V> _ <V> | Between vowels\nt | tʰ | # _ | \na | ɐ | _ . | \n}"
+        );
+    }
+
+    #[test]
+    fn classes_can_include_classes() {
+        let mut diagnostics = Diagnostics::default();
+        let rewrite = Rewrite::parse(
+            "C = p t\nV = a i\nL = <C> <V> l\nX = <Y>\n<L> > x / _ #",
+            "rules",
+            &[],
+            &mut diagnostics,
+        );
+        assert_eq!(rewrite.rules.len(), 1);
+        assert_eq!(rewrite.apply("kal"), "kax");
+        assert_eq!(rewrite.apply("kat"), "kax");
+        assert_eq!(rewrite.apply("kak"), "kak");
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "error: rules:4: unknown class `<Y>`",
+                "error: rules:4: class has no letters",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors_work() {
+        let mut diagnostics = Diagnostics::default();
+        Rewrite::parse(
+            "V = a i\nS = p t\nx\n> a\na > b / c\n<V> > <S>\na > <S>\nb > <X>\na# > a\n",
+            "rules",
+            &[],
+            &mut diagnostics,
+        );
+        let messages = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "error: rules:3: expected class such as `V = a i u`, or rule such as `a > b / _ c`",
+                "error: rules:4: rule must replace at least one letter",
+                "error: rules:5: environment must have `_`, such as `/ a _ b`",
+                "error: rules:7: class 1 of `to` is not in `from`",
+                "error: rules:8: unknown class `<X>`",
+                "error: rules:9: `#` can only be in environment",
+            ]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    rewrite::{self, Rewrite, Rule},
+    word_list::{Column, WordList},
+    Config,
+};
+
+/// Ordered sound changes from a proto-language, parsed from file in `files.sound_changes`
+///
+/// ```txt
+/// V = a e i o u
+/// Stop = p t k b d g
+/// Fric = f θ x v ð ɣ
+/// Voiced = b d g v ð ɣ
+///
+/// @stage Old Examplelang
+/// # Lenition
+/// <Stop&Voiced> > <Fric&Voiced> / <V> _ <V>
+/// ? e > / _ #
+///
+/// @stage Modern Examplelang
+/// @if northern
+/// k > tʃ / _ i
+/// @end
+/// ```
+///
+/// Rules have the syntax of `Rewrite`, and are grouped into stages, named after the sound changes
+/// until the first `@stage`. Rules starting with `?` are optional, so words keep both forms, and
+/// rules between `@if flag` (or `@if !flag`) and `@end` only apply with flag set
+#[derive(Debug)]
+pub struct SoundChanges {
+    /// Name of file, without extension
+    pub name: String,
+    pub rewrite: Rewrite,
+    pub stages: Vec<Stage>,
+}
+
+#[derive(Debug)]
+pub struct Stage {
+    pub name: String,
+    pub changes: Vec<Change>,
+}
+
+/// Rule of stage, by index of rule in rewrite
+#[derive(Debug, Clone)]
+pub struct Change {
+    rule: usize,
+    optional: bool,
+    /// Flags which must be set, or not set with `true`
+    conditions: Vec<(String, bool)>,
+}
+
+/// Forms of word after each stage, with rules which changed them
+pub struct Derivation<'a> {
+    pub word: String,
+    pub stages: Vec<StageForms<'a>>,
+}
+
+pub struct StageForms<'a> {
+    pub name: &'a str,
+    /// Forms after stage, more than one if an optional rule applied
+    pub forms: Vec<String>,
+    /// Each rule which changed a form, with forms after it
+    pub trace: Vec<(&'a Rule, Vec<String>)>,
+}
+
+impl SoundChanges {
+    /// Parse each file in sound changes directory of project, sorted by name
+    pub fn load_all(config: &Config, diagnostics: &mut Diagnostics) -> Vec<Self> {
+        let dir = Path::new(&config.files.source).join(&config.files.sound_changes);
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut paths = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        paths
+            .iter()
+            .map(|path| {
+                let file = fs::read_to_string(path).expect("Could not read sound changes file");
+                let filename = path.file_name().unwrap_or_default().to_string_lossy();
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                SoundChanges::parse(
+                    &file,
+                    &format!("{}/{}", config.files.sound_changes, filename),
+                    &name,
+                    diagnostics,
+                )
+            })
+            .collect()
+    }
+
+    pub fn parse(file: &str, filepath: &str, name: &str, diagnostics: &mut Diagnostics) -> Self {
+        let mut stages = Vec::<Stage>::new();
+        // Lines of `@if`, with condition
+        let mut conditions = Vec::<(usize, (String, bool))>::new();
+        // Change of rule on each line, with index of stage
+        let mut changes = HashMap::<usize, (usize, Change)>::new();
+        // File without directives or `?`, with same line numbers, for rewrite rules
+        let mut rules = String::new();
+
+        for (line_num, line) in file.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: &str| Diagnostic::error(filepath, Some(line_num + 1), message);
+
+            if let Some(directive) = line.strip_prefix('@') {
+                let (keyword, arg) = directive
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((directive, ""));
+                let arg = arg.trim();
+                match keyword {
+                    "stage" if arg.is_empty() => {
+                        diagnostics.push(error("stage must have a name, such as `@stage Old`"))
+                    }
+                    "stage" => stages.push(Stage {
+                        name: arg.to_string(),
+                        changes: Vec::new(),
+                    }),
+                    "if" if arg.is_empty() || arg.contains(char::is_whitespace) => {
+                        diagnostics.push(error("condition must be a flag, such as `@if flag`"))
+                    }
+                    "if" => {
+                        let condition = match arg.strip_prefix('!') {
+                            Some(flag) => (flag.to_string(), true),
+                            None => (arg.to_string(), false),
+                        };
+                        conditions.push((line_num + 1, condition));
+                    }
+                    "end" => {
+                        if conditions.pop().is_none() {
+                            diagnostics.push(error("`@end` without `@if`"));
+                        }
+                    }
+                    _ => diagnostics.push(error(
+                        "unknown directive, expected `@stage`, `@if` or `@end`",
+                    )),
+                }
+                rules.push('\n');
+                continue;
+            }
+
+            let (optional, line) = match line.strip_prefix('?') {
+                Some(line) => (true, line.trim()),
+                None => (false, line),
+            };
+            if rewrite::is_rule(line) {
+                // Rules before first stage are in stage named after sound changes
+                if stages.is_empty() {
+                    stages.push(Stage {
+                        name: name.to_string(),
+                        changes: Vec::new(),
+                    });
+                }
+                let change = Change {
+                    rule: 0,
+                    optional,
+                    conditions: conditions.iter().map(|(_, c)| c.clone()).collect(),
+                };
+                changes.insert(line_num + 1, (stages.len() - 1, change));
+            }
+            rules.push_str(line);
+            rules.push('\n');
+        }
+
+        for (line, _) in conditions {
+            diagnostics.push(Diagnostic::error(
+                filepath,
+                Some(line),
+                "`@if` is never closed with `@end`",
+            ));
+        }
+
+        let rewrite = Rewrite::parse(&rules, filepath, &[], diagnostics);
+        for (i, rule) in rewrite.rules.iter().enumerate() {
+            if let Some((stage, change)) = changes.get(&rule.line) {
+                stages[*stage].changes.push(Change {
+                    rule: i,
+                    ..change.clone()
+                });
+            }
+        }
+
+        SoundChanges {
+            name: name.to_string(),
+            rewrite,
+            stages,
+        }
+    }
+
+    /// Apply each stage to word in order, with rules whose conditions are met by `flags`
+    pub fn derive(&self, word: &str, flags: &[String]) -> Derivation<'_> {
+        let mut forms = vec![self.rewrite.letters(word).concat()];
+        let mut stages = Vec::new();
+
+        for stage in &self.stages {
+            let mut trace = Vec::new();
+            for change in &stage.changes {
+                let applies = change
+                    .conditions
+                    .iter()
+                    .all(|(flag, negate)| flags.contains(flag) != *negate);
+                if !applies {
+                    continue;
+                }
+
+                let rule = &self.rewrite.rules[change.rule];
+                let mut next = Vec::<String>::new();
+                for form in &forms {
+                    let changed = rule.apply(&self.rewrite.letters(form)).concat();
+                    if change.optional && changed != *form && !next.contains(form) {
+                        next.push(form.clone());
+                    }
+                    if !next.contains(&changed) {
+                        next.push(changed);
+                    }
+                }
+                if next != forms {
+                    trace.push((rule, next.clone()));
+                }
+                forms = next;
+            }
+            stages.push(StageForms {
+                name: &stage.name,
+                forms: forms.clone(),
+                trace,
+            });
+        }
+
+        Derivation {
+            word: word.to_string(),
+            stages,
+        }
+    }
+
+    /// Word list with column of each stage, after column of words
+    ///
+    /// Words are from `word` column of list, or first column, and `gloss` and `note` columns are
+    /// kept
+    pub fn derive_list(&self, list: &WordList, flags: &[String]) -> WordList {
+        let column = |column: &Column| list.columns.iter().position(|other| other == column);
+        let word = column(&Column::Word).unwrap_or(0);
+        let kept = [Column::Gloss, Column::Note]
+            .into_iter()
+            .filter_map(|kept| Some((column(&kept)?, kept)))
+            .collect::<Vec<_>>();
+
+        let words = list
+            .rows
+            .iter()
+            .map(|row| row.get(word).cloned().unwrap_or_default())
+            .collect::<Vec<_>>();
+        let mut derived =
+            self.derive_table(&words.iter().map(String::as_str).collect::<Vec<_>>(), flags);
+        for (row, cells) in derived.rows.iter_mut().zip(&list.rows) {
+            for (i, _) in &kept {
+                row.push(cells.get(*i).cloned().unwrap_or_default());
+            }
+        }
+        derived
+            .columns
+            .extend(kept.into_iter().map(|(_, column)| column));
+        derived.title = list.title.clone();
+        derived
+    }
+
+    /// Word list with a row for each word, with its forms after each stage
+    ///
+    /// Forms of optional rules are separated with `~`
+    pub fn derive_table(&self, words: &[&str], flags: &[String]) -> WordList {
+        let mut columns = vec![Column::Word];
+        columns.extend(
+            self.stages
+                .iter()
+                .map(|stage| Column::Stage(stage.name.clone())),
+        );
+
+        let rows = words
+            .iter()
+            .map(|word| {
+                let derivation = self.derive(word.trim_start_matches('*'), flags);
+                let mut row = vec![word.to_string()];
+                row.extend(
+                    derivation
+                        .stages
+                        .iter()
+                        .map(|stage| stage.forms.join(" ~ ")),
+                );
+                row
+            })
+            .collect();
+
+        WordList {
+            title: None,
+            columns,
+            rows,
+        }
+    }
+}
+
+impl fmt::Display for Derivation<'_> {
+    /// Word and forms after each stage, then each rule which changed word, for terminal
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut forms = vec![self.word.clone()];
+        forms.extend(self.stages.iter().map(|stage| stage.forms.join(" ~ ")));
+        forms.dedup();
+        writeln!(f, "{}", forms.join(" > "))?;
+
+        for stage in &self.stages {
+            writeln!(f, "  {}", stage.name)?;
+            for (rule, forms) in &stage.trace {
+                writeln!(f, "    {}: {}", rule.text, forms.join(" ~ "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANGES: &str = "\
+        V = a e i o u\n\
+        Stop = p t k b d g\n\
+        Fric = f θ x v ð ɣ\n\
+        Voiced = b d g v ð ɣ\n\
+        \n\
+        p > f / # _\n\
+        @stage Old\n\
+        <Stop&Voiced> > <Fric&Voiced> / <V> _ <V>\n\
+        ? e > ∅ / _ #\n\
+        @stage Modern\n\
+        @if northern\n\
+        k > tʃ / _ i\n\
+        @end\n\
+        @if !northern\n\
+        <Stop&!Voiced> > <Stop&Voiced> / <V> _ <V>\n\
+        @end\n";
+
+    #[test]
+    fn derive_works() {
+        let mut diagnostics = Diagnostics::default();
+        let changes =
+            SoundChanges::parse(CHANGES, "sound-changes/modern", "modern", &mut diagnostics);
+        assert!(diagnostics.report().is_ok());
+        assert_eq!(
+            changes
+                .stages
+                .iter()
+                .map(|stage| (stage.name.as_str(), stage.changes.len()))
+                .collect::<Vec<_>>(),
+            vec![("modern", 1), ("Old", 2), ("Modern", 2)]
+        );
+
+        let derivation = changes.derive("pidake", &[]);
+        assert_eq!(derivation.stages[2].forms, ["fiðage", "fiðak"]);
+        assert_eq!(
+            derivation.to_string(),
+            "pidake > fidake > fiðake ~ fiðak > fiðage ~ fiðak\n  \
+            modern\n    p > f / # _: fidake\n  \
+            Old\n    <Stop&Voiced> > <Fric&Voiced> / <V> _ <V>: fiðake\n    \
+            e > ∅ / _ #: fiðake ~ fiðak\n  \
+            Modern\n    <Stop&!Voiced> > <Stop&Voiced> / <V> _ <V>: fiðage ~ fiðak\n"
+        );
+        assert_eq!(
+            changes.derive("taki", &["northern".to_string()]).stages[2].forms,
+            ["tatʃi"]
+        );
+
+        let table = changes.derive_table(&["*pata"], &[]);
+        assert_eq!(
+            table.to_table(),
+            "{|\n'Word |'modern |'Old |'Modern\n*pata | fata | fata | fada\n}"
+        );
+        assert_eq!(
+            table.to_table_with_header(&Column::Word, "'Proto"),
+            "{|\n'Proto |'modern |'Old |'Modern\n*pata | fata | fata | fada\n}"
+        );
+        assert_eq!(
+            table.to_llst(),
+            "word | modern | Old | Modern\n*pata | fata | fata | fada\n"
+        );
+    }
+
+    #[test]
+    fn parse_errors_work() {
+        let mut diagnostics = Diagnostics::default();
+        SoundChanges::parse(
+            "@stage\n@end\n@if a b\n@if a\n@else\na > b",
+            "sound-changes/modern",
+            "modern",
+            &mut diagnostics,
+        );
+        let messages = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "error: sound-changes/modern:1: stage must have a name, such as `@stage Old`",
+                "error: sound-changes/modern:2: `@end` without `@if`",
+                "error: sound-changes/modern:3: condition must be a flag, such as `@if flag`",
+                "error: sound-changes/modern:5: unknown directive, expected `@stage`, `@if` or `@end`",
+                "error: sound-changes/modern:4: `@if` is never closed with `@end`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,743 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use html_escape::encode_quoted_attribute as escape_html;
+
+use crate::MyError;
+
+/// Value that can be used in a template
+#[derive(Debug, Clone)]
+pub enum Value {
+    /// Plain text, escaped when output
+    Text(String),
+    /// Trusted html, output as is
+    Html(String),
+    Bool(bool),
+    List(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+    /// Defined, but empty
+    Null,
+}
+
+impl Value {
+    /// Create object value from list of fields
+    pub fn object<const N: usize>(fields: [(&str, Value); N]) -> Self {
+        Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// Whether value counts as true in a condition
+    fn is_truthy(&self) -> bool {
+        use Value::*;
+
+        match self {
+            Text(string) | Html(string) => !string.is_empty(),
+            Bool(bool) => *bool,
+            List(list) => !list.is_empty(),
+            Object(_) => true,
+            Null => false,
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Value::Text(string.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Self {
+        Value::Text(string)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(bool: bool) -> Self {
+        Value::Bool(bool)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(option: Option<T>) -> Self {
+        match option {
+            Some(value) => value.into(),
+            None => Value::Null,
+        }
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(list: Vec<T>) -> Self {
+        Value::List(list.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Line and column of tag in template file
+#[derive(Debug, Clone, Copy)]
+struct Pos {
+    line: usize,
+    col: usize,
+}
+
+/// Variable path, such as `page.title`, optionally negated with `not`
+#[derive(Debug)]
+struct Expr {
+    path: Vec<String>,
+    negate: bool,
+    pos: Pos,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    /// `{$ path }`
+    Output(Expr),
+    /// `{% if path %} ... {% else %} ... {% end %}`
+    If {
+        cond: Expr,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    /// `{% for name in path %} ... {% end %}`
+    For {
+        name: String,
+        list: Expr,
+        body: Vec<Node>,
+    },
+    /// `{% include "file.html" %}`
+    Include(Template),
+    /// `{% block name %} ... {% end %}`, can be overridden by templates which extend this one
+    Block {
+        name: String,
+        body: Vec<Node>,
+    },
+}
+
+/// Token of template source, before nesting
+enum Token {
+    Text(String),
+    Output(String, Pos),
+    Tag(String, Pos),
+}
+
+/// Tag which ended a block (`else` or `end`), if any
+type ClosingTag = Option<(String, Pos)>;
+
+/// Blocks overridden by child templates, with name of template which defined them
+type Blocks<'a> = HashMap<&'a str, (&'a str, &'a [Node])>;
+
+/// Parsed template file
+#[derive(Debug)]
+pub struct Template {
+    name: String,
+    nodes: Vec<Node>,
+    /// Template given with `{% extends "file.html" %}`
+    parent: Option<Box<Template>>,
+}
+
+impl Template {
+    /// Read and parse template file, including any partials
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::load_nested(path, &mut Vec::new())
+    }
+
+    fn load_nested(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        let name = path.to_string_lossy().to_string();
+
+        if stack.iter().any(|parent| parent == path) {
+            return Err(Box::new(MyError(format!(
+                "{}: template includes or extends itself",
+                name
+            ))));
+        }
+
+        let source = fs::read_to_string(path)
+            .map_err(|err| MyError(format!("{}: could not read template: {}", name, err)))?;
+
+        stack.push(path.to_path_buf());
+        let mut parser = Parser {
+            name: &name,
+            dir: path.parent().unwrap_or_else(|| Path::new(".")),
+            stack,
+            parent: None,
+        };
+        let nodes = parser.parse(&source);
+        let parent = parser.parent.take();
+        stack.pop();
+
+        Ok(Template {
+            nodes: nodes?,
+            parent: parent.map(Box::new),
+            name,
+        })
+    }
+
+    /// Parse template from string, without reading any files
+    ///
+    /// Includes are resolved relative to current directory
+    #[cfg(test)]
+    fn parse(name: &str, source: &str) -> Result<Self, Box<dyn Error>> {
+        let mut parser = Parser {
+            name,
+            dir: Path::new("."),
+            stack: &mut Vec::new(),
+            parent: None,
+        };
+        Ok(Template {
+            nodes: parser.parse(source)?,
+            parent: parser.parent.take().map(Box::new),
+            name: name.to_string(),
+        })
+    }
+
+    /// Render template with context object
+    pub fn render(&self, context: &Value) -> Result<String, Box<dyn Error>> {
+        let mut output = String::new();
+        self.render_with_blocks(context, HashMap::new(), &mut output)?;
+        Ok(output)
+    }
+
+    /// Render template, with blocks overridden by child templates
+    ///
+    /// If template extends another, the parent is rendered instead, using blocks from this template
+    fn render_with_blocks<'a>(
+        &'a self,
+        context: &Value,
+        mut blocks: Blocks<'a>,
+        output: &mut String,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = &self.parent {
+            collect_blocks(&self.name, &self.nodes, &mut blocks);
+            return parent.render_with_blocks(context, blocks, output);
+        }
+
+        Renderer {
+            name: &self.name,
+            context,
+            scopes: Vec::new(),
+            blocks,
+        }
+        .render(&self.nodes, output)
+    }
+}
+
+/// Add all blocks in nodes, which are not already overridden
+fn collect_blocks<'a>(name: &'a str, nodes: &'a [Node], blocks: &mut Blocks<'a>) {
+    for node in nodes {
+        match node {
+            Node::Block { name: block, body } => {
+                blocks.entry(block).or_insert((name, body));
+                collect_blocks(name, body, blocks);
+            }
+            Node::If {
+                then, otherwise, ..
+            } => {
+                collect_blocks(name, then, blocks);
+                collect_blocks(name, otherwise, blocks);
+            }
+            Node::For { body, .. } => collect_blocks(name, body, blocks),
+            _ => (),
+        }
+    }
+}
+
+/// Create error with location in template file
+fn error_at(name: &str, pos: Pos, message: impl Into<String>) -> Box<dyn Error> {
+    Box::new(MyError(format!(
+        "{}:{}:{}: {}",
+        name,
+        pos.line,
+        pos.col,
+        message.into()
+    )))
+}
+
+struct Parser<'a> {
+    name: &'a str,
+    dir: &'a Path,
+    stack: &'a mut Vec<PathBuf>,
+    parent: Option<Template>,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(&mut self, source: &str) -> Result<Vec<Node>, Box<dyn Error>> {
+        let mut tokens = self.tokenize(source)?.into_iter();
+
+        let (nodes, end) = self.parse_nodes(&mut tokens)?;
+        if let Some((tag, pos)) = end {
+            return Err(error_at(
+                self.name,
+                pos,
+                format!("unexpected `{{% {} %}}`", tag),
+            ));
+        }
+
+        Ok(nodes)
+    }
+
+    /// Split source into text, output statements, and tags
+    fn tokenize(&self, source: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+        let mut tokens = Vec::new();
+        let mut text = String::new();
+        let mut rest = source;
+        let mut pos = Pos { line: 1, col: 1 };
+
+        while !rest.is_empty() {
+            let closing = if rest.starts_with("{$") {
+                "}"
+            } else if rest.starts_with("{%") {
+                "%}"
+            } else {
+                // Unwrap should not fail, as rest is not empty
+                let ch = rest.chars().next().unwrap();
+                text.push(ch);
+                advance(&mut pos, ch);
+                rest = &rest[ch.len_utf8()..];
+                continue;
+            };
+
+            let end = match rest[2..].find(closing) {
+                Some(end) => end + 2,
+                None => return Err(error_at(self.name, pos, "unclosed tag")),
+            };
+            let inner = rest[2..end].trim().to_string();
+
+            if !text.is_empty() {
+                tokens.push(Token::Text(std::mem::take(&mut text)));
+            }
+            tokens.push(if closing == "}" {
+                Token::Output(inner, pos)
+            } else {
+                Token::Tag(inner, pos)
+            });
+
+            let tag_len = end + closing.len();
+            rest[..tag_len].chars().for_each(|ch| advance(&mut pos, ch));
+            rest = &rest[tag_len..];
+        }
+
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+
+        Ok(tokens)
+    }
+
+    /// Parse nodes until end of tokens, or a closing tag (`else` or `end`)
+    ///
+    /// Returns closing tag, if any
+    fn parse_nodes(
+        &mut self,
+        tokens: &mut impl Iterator<Item = Token>,
+    ) -> Result<(Vec<Node>, ClosingTag), Box<dyn Error>> {
+        let mut nodes = Vec::new();
+
+        while let Some(token) = tokens.next() {
+            let (tag, pos) = match token {
+                Token::Text(text) => {
+                    nodes.push(Node::Text(text));
+                    continue;
+                }
+                Token::Output(expr, pos) => {
+                    nodes.push(Node::Output(self.parse_expr(&expr, pos)?));
+                    continue;
+                }
+                Token::Tag(tag, pos) => (tag, pos),
+            };
+
+            let (keyword, args) = match tag.find(char::is_whitespace) {
+                Some(i) => (&tag[..i], tag[i..].trim()),
+                None => (tag.as_str(), ""),
+            };
+
+            match keyword {
+                "else" | "end" => return Ok((nodes, Some((keyword.to_string(), pos)))),
+
+                "if" => {
+                    let cond = self.parse_expr(args, pos)?;
+                    let (then, end) = self.parse_nodes(tokens)?;
+                    let otherwise = match end {
+                        Some((end, _)) if end == "else" => {
+                            let (otherwise, end) = self.parse_nodes(tokens)?;
+                            self.expect_end(end, pos, "if")?;
+                            otherwise
+                        }
+                        end => {
+                            self.expect_end(end, pos, "if")?;
+                            Vec::new()
+                        }
+                    };
+                    nodes.push(Node::If {
+                        cond,
+                        then,
+                        otherwise,
+                    });
+                }
+
+                "for" => {
+                    let (name, list) = match args.split_once(" in ") {
+                        Some((name, list)) if is_ident(name.trim()) => (name.trim(), list),
+                        _ => {
+                            return Err(error_at(
+                                self.name,
+                                pos,
+                                "expected `{% for <name> in <variable> %}`",
+                            ))
+                        }
+                    };
+                    let list = self.parse_expr(list, pos)?;
+                    let (body, end) = self.parse_nodes(tokens)?;
+                    self.expect_end(end, pos, "for")?;
+                    nodes.push(Node::For {
+                        name: name.to_string(),
+                        list,
+                        body,
+                    });
+                }
+
+                "include" => {
+                    let filename = parse_string(args)
+                        .ok_or_else(|| error_at(self.name, pos, "expected quoted filename"))?;
+                    let partial = Template::load_nested(&self.dir.join(filename), self.stack)
+                        .map_err(|err| error_at(self.name, pos, err.to_string()))?;
+                    nodes.push(Node::Include(partial));
+                }
+
+                "extends" => {
+                    let is_first = self.parent.is_none()
+                        && nodes.iter().all(|node| match node {
+                            Node::Text(text) => text.trim().is_empty(),
+                            _ => false,
+                        });
+                    if !is_first {
+                        return Err(error_at(
+                            self.name,
+                            pos,
+                            "`extends` must be first tag in template",
+                        ));
+                    }
+
+                    let filename = parse_string(args)
+                        .ok_or_else(|| error_at(self.name, pos, "expected quoted filename"))?;
+                    let parent = Template::load_nested(&self.dir.join(filename), self.stack)
+                        .map_err(|err| error_at(self.name, pos, err.to_string()))?;
+                    self.parent = Some(parent);
+                }
+
+                "block" => {
+                    if !is_ident(args) {
+                        return Err(error_at(self.name, pos, "expected `{% block <name> %}`"));
+                    }
+                    let (body, end) = self.parse_nodes(tokens)?;
+                    self.expect_end(end, pos, "block")?;
+                    nodes.push(Node::Block {
+                        name: args.to_string(),
+                        body,
+                    });
+                }
+
+                _ => {
+                    return Err(error_at(
+                        self.name,
+                        pos,
+                        format!("unknown tag `{}`", keyword),
+                    ))
+                }
+            }
+        }
+
+        Ok((nodes, None))
+    }
+
+    /// Check that block was closed with `end`
+    fn expect_end(&self, end: ClosingTag, start: Pos, block: &str) -> Result<(), Box<dyn Error>> {
+        match end {
+            Some((end, _)) if end == "end" => Ok(()),
+            Some((end, pos)) => Err(error_at(
+                self.name,
+                pos,
+                format!("unexpected `{{% {} %}}` in `{}` block", end, block),
+            )),
+            None => Err(error_at(
+                self.name,
+                start,
+                format!("`{}` block is never closed with `{{% end %}}`", block),
+            )),
+        }
+    }
+
+    fn parse_expr(&self, expr: &str, pos: Pos) -> Result<Expr, Box<dyn Error>> {
+        let (negate, path) = match expr.strip_prefix("not ") {
+            Some(path) => (true, path.trim()),
+            None => (false, expr.trim()),
+        };
+
+        let path: Vec<String> = path.split('.').map(str::to_string).collect();
+        if !path.iter().all(|part| is_ident(part)) {
+            return Err(error_at(
+                self.name,
+                pos,
+                format!("invalid variable `{}`", expr),
+            ));
+        }
+
+        Ok(Expr { path, negate, pos })
+    }
+}
+
+/// Move position past character
+fn advance(pos: &mut Pos, ch: char) {
+    if ch == '\n' {
+        pos.line += 1;
+        pos.col = 1;
+    } else {
+        pos.col += 1;
+    }
+}
+
+/// Variable or field name, only alphanumeric characters, `_`, and `-`
+fn is_ident(string: &str) -> bool {
+    !string.is_empty()
+        && string
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-')
+}
+
+/// Remove double quotes from around string
+fn parse_string(string: &str) -> Option<&str> {
+    string.strip_prefix('"')?.strip_suffix('"')
+}
+
+struct Renderer<'a> {
+    name: &'a str,
+    context: &'a Value,
+    /// Loop variables, innermost last
+    scopes: Vec<(String, Value)>,
+    blocks: Blocks<'a>,
+}
+
+impl<'a> Renderer<'a> {
+    fn render(&mut self, nodes: &'a [Node], output: &mut String) -> Result<(), Box<dyn Error>> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+
+                Node::Output(expr) => match self.lookup(expr)? {
+                    Value::Text(text) => output.push_str(&escape_html(&text)),
+                    Value::Html(html) => output.push_str(&html),
+                    Value::Bool(bool) => output.push_str(&bool.to_string()),
+                    Value::Null => (),
+                    Value::List(_) | Value::Object(_) => {
+                        return Err(error_at(
+                            self.name,
+                            expr.pos,
+                            format!("cannot output list or object `{}`", expr.path.join(".")),
+                        ))
+                    }
+                },
+
+                Node::If {
+                    cond,
+                    then,
+                    otherwise,
+                } => {
+                    if self.lookup(cond)?.is_truthy() != cond.negate {
+                        self.render(then, output)?;
+                    } else {
+                        self.render(otherwise, output)?;
+                    }
+                }
+
+                Node::For { name, list, body } => {
+                    let items = match self.lookup(list)? {
+                        Value::List(items) => items,
+                        Value::Null => Vec::new(),
+                        _ => {
+                            return Err(error_at(
+                                self.name,
+                                list.pos,
+                                format!("`{}` is not a list", list.path.join(".")),
+                            ))
+                        }
+                    };
+
+                    for item in items {
+                        self.scopes.push((name.clone(), item));
+                        let result = self.render(body, output);
+                        self.scopes.pop();
+                        result?;
+                    }
+                }
+
+                Node::Include(partial) => {
+                    let mut renderer = Renderer {
+                        name: &partial.name,
+                        context: self.context,
+                        scopes: self.scopes.clone(),
+                        blocks: self.blocks.clone(),
+                    };
+                    renderer.render(&partial.nodes, output)?;
+                }
+
+                Node::Block { name, body } => {
+                    let (block_name, body) = self
+                        .blocks
+                        .get(name.as_str())
+                        .copied()
+                        .unwrap_or((self.name, body));
+
+                    let name = std::mem::replace(&mut self.name, block_name);
+                    let result = self.render(body, output);
+                    self.name = name;
+                    result?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get value of variable, searching loop variables first
+    fn lookup(&self, expr: &Expr) -> Result<Value, Box<dyn Error>> {
+        let (first, rest) = expr.path.split_first().expect("Path should not be empty");
+
+        let root = self
+            .scopes
+            .iter()
+            .rev()
+            .find(|(name, _)| name == first)
+            .map(|(_, value)| value)
+            .or_else(|| match self.context {
+                Value::Object(fields) => fields.get(first),
+                _ => None,
+            });
+
+        let value = rest.iter().try_fold(root, |value, field| match value {
+            Some(Value::Object(fields)) => Ok(fields.get(field)),
+            _ => Err(()),
+        });
+
+        match value {
+            Ok(Some(value)) => Ok(value.clone()),
+            _ => Err(error_at(
+                self.name,
+                expr.pos,
+                format!("undefined variable `{}`", expr.path.join(".")),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, context: &Value) -> Result<String, Box<dyn Error>> {
+        Template::parse("test.html", source)?.render(context)
+    }
+
+    #[test]
+    fn output_escapes_text() {
+        let context = Value::object([
+            ("text", Value::from("<b>")),
+            ("html", Value::Html("<b>".to_string())),
+            ("page", Value::object([("title", Value::from("a & b"))])),
+        ]);
+
+        assert_eq!(
+            render("{$text} {$ html } {$page.title}", &context).unwrap(),
+            "&lt;b&gt; <b> a &amp; b"
+        );
+    }
+
+    #[test]
+    fn conditions_and_loops_work() {
+        let context = Value::object([
+            ("yes", Value::from(true)),
+            ("empty", Value::from("")),
+            ("list", Value::from(vec!["a", "b", "c"])),
+        ]);
+
+        assert_eq!(
+            render(
+                "{% if yes %}1{% else %}2{% end %}\
+                {% if empty %}3{% else %}4{% end %}\
+                {% if not empty %}5{% end %}",
+                &context
+            )
+            .unwrap(),
+            "145"
+        );
+
+        assert_eq!(
+            render("{% for x in list %}[{$x}]{% end %}", &context).unwrap(),
+            "[a][b][c]"
+        );
+    }
+
+    #[test]
+    fn blocks_can_be_overridden() {
+        // Unique to process, so parallel test runs do not share files
+        let dir =
+            std::env::temp_dir().join(format!("lingdocs-template-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("base.html"),
+            "<title>{% block title %}Base{% end %}</title>{% block body %}{% end %}",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("page.html"),
+            "{% extends \"base.html\" %}{% block body %}<p>{$ text }</p>{% end %}",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("child.html"),
+            "{% extends \"page.html\" %}{% block title %}Child{% end %}",
+        )
+        .unwrap();
+
+        let context = Value::object([("text", Value::from("abc"))]);
+        assert_eq!(
+            Template::load(&dir.join("child.html"))
+                .unwrap()
+                .render(&context)
+                .unwrap(),
+            "<title>Child</title><p>abc</p>"
+        );
+
+        assert!(Template::parse("test.html", "abc{$ text }{% extends \"base.html\" %}").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn errors_have_location() {
+        let context = Value::object([]);
+
+        assert_eq!(
+            render("abc\n  {$ page.missing }", &context)
+                .unwrap_err()
+                .to_string(),
+            "test.html:2:3: undefined variable `page.missing`"
+        );
+
+        assert_eq!(
+            render("{% if x %}", &context).unwrap_err().to_string(),
+            "test.html:1:1: `if` block is never closed with `{% end %}`"
+        );
+
+        assert_eq!(
+            render("{% end %}", &context).unwrap_err().to_string(),
+            "test.html:1:1: unexpected `{% end %}`"
+        );
+    }
+}
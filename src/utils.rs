@@ -1,3 +1,5 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 /// Separate filename into name and extension, separately
 ///
 /// All characters after last dot are included in extension
@@ -21,6 +23,100 @@ pub fn separate_filename_ext(filename: &str) -> (String, &str) {
     }
 }
 
+/// Find index of brace which closes brace at start of string
+///
+/// Nested braces and escaped characters are skipped
+pub fn find_closing_brace(string: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut is_escaped = false;
+
+    for (i, ch) in string.char_indices() {
+        if !is_escaped {
+            match ch {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                }
+                _ => (),
+            }
+        }
+        is_escaped = ch == '\\' && !is_escaped;
+    }
+
+    None
+}
+
+/// Resolve path relative to directory of file, where both are relative to same root
+///
+/// Returns `None` if path goes outside of root
+pub fn join_relative(filepath: &str, path: &str) -> Option<String> {
+    let mut parts: Vec<&str> = filepath.split('/').collect();
+    // Remove filename
+    parts.pop();
+
+    for part in path.split('/') {
+        match part {
+            "" | "." => (),
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    Some(parts.join("/"))
+}
+
+/// Relative path from file to root, such as `./` or `../../`, where file is relative to root
+pub fn relative_root(filepath: &str) -> String {
+    match filepath.matches('/').count() {
+        0 => "./".to_string(),
+        depth => "../".repeat(depth),
+    }
+}
+
+/// Current date as `YYYY-MM-DD`, in UTC
+///
+/// Uses `SOURCE_DATE_EPOCH` environment variable if set, for reproducible builds
+pub fn today() -> String {
+    let secs = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(secs) => secs.parse().unwrap_or(0),
+        Err(_) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0),
+    };
+
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Convert days since Unix epoch to year, month, and day
+///
+/// Algorithm from http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -41,4 +137,26 @@ mod tests {
             ("abc.def.ghi".to_string(), "jkl")
         );
     }
+
+    #[test]
+    fn join_relative_works() {
+        assert_eq!(join_relative("a.ling", "b.ling").as_deref(), Some("b.ling"));
+        assert_eq!(
+            join_relative("dir/a.ling", "./b.ling").as_deref(),
+            Some("dir/b.ling")
+        );
+        assert_eq!(
+            join_relative("dir/a.ling", "../other/b.ling").as_deref(),
+            Some("other/b.ling")
+        );
+        assert_eq!(join_relative("a.ling", "../b.ling"), None);
+    }
+
+    #[test]
+    fn civil_from_days_works() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(365), (1971, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
 }
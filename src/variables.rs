@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    case,
+    diagnostic::{Diagnostic, Diagnostics},
+    Config,
+};
+
+/// Variables available to `{$name}` statements
+///
+/// Built-in variables from package, then `[variables]` table, which can override built-ins
+pub fn project_variables(config: &Config) -> BTreeMap<String, String> {
+    let package = &config.package;
+
+    let mut variables = BTreeMap::from([
+        ("name".to_string(), package.name.clone()),
+        ("title".to_string(), case::upper_first(&package.name)),
+        ("code".to_string(), package.code.clone()),
+    ]);
+    if let Some(author) = &package.author {
+        variables.insert("author".to_string(), author.clone());
+    }
+
+    variables.extend(config.variables.clone());
+    variables
+}
+
+/// Replace `{$name}` statements in `.ling` file with value of variable
+///
+/// `{$$name}` is not replaced, and is later rendered as literal `{$name}`
+///
+/// Undefined variables are reported, and left unchanged
+pub fn substitute(
+    file: &str,
+    filepath: &str,
+    variables: &BTreeMap<String, String>,
+    diagnostics: &mut Diagnostics,
+) -> String {
+    let mut output = String::new();
+    let mut rest = file;
+    let mut line = 1;
+
+    while let Some(start) = rest.find("{$") {
+        let (before, statement) = rest.split_at(start);
+        output.push_str(before);
+        line += before.matches('\n').count();
+
+        let end = match statement.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        let name = statement[2..end].trim();
+
+        // Escaped with backslash, or with double `$`
+        if before.ends_with('\\') || name.starts_with('$') {
+            output.push_str(&statement[..=end]);
+        } else if let Some(value) = variables.get(name) {
+            output.push_str(value);
+        } else {
+            diagnostics.push(Diagnostic::error(
+                filepath,
+                Some(line),
+                format!("undefined variable `{}`", name),
+            ));
+            output.push_str(&statement[..=end]);
+        }
+
+        line += statement[..=end].matches('\n').count();
+        rest = &statement[end + 1..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitute_works() {
+        let variables = BTreeMap::from([("name".to_string(), "abc".to_string())]);
+        let mut diagnostics = Diagnostics::default();
+
+        assert_eq!(
+            substitute(
+                "a {$name} {$ name } \\{$name} {$$name}",
+                "test.ling",
+                &variables,
+                &mut diagnostics
+            ),
+            "a abc abc \\{$name} {$$name}"
+        );
+        assert!(diagnostics.report().is_ok());
+
+        assert_eq!(
+            substitute("a\nb {$missing}", "test.ling", &variables, &mut diagnostics),
+            "a\nb {$missing}"
+        );
+        assert_eq!(
+            diagnostics.iter().next().unwrap().to_string(),
+            "error: test.ling:2: undefined variable `missing`"
+        );
+        assert_eq!(
+            diagnostics.report().unwrap_err().to_string(),
+            "Build failed with 1 error"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use crate::diagnostic::{Diagnostic, Diagnostics};
+
+/// Word list, parsed from `.llst` file
+///
+/// ```txt
+/// # Colours
+/// word | ipa     | gloss | note
+/// ruĝa | ˈru.dʒa | red   |
+/// blua | ˈblu.a  | blue  | Also means sad
+/// ```
+///
+/// Optional title, then header row with a name for each column, then a row for each word or phrase
+pub struct WordList {
+    pub title: Option<String>,
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Column of word list, from name in header row
+#[derive(Debug, PartialEq)]
+pub enum Column {
+    /// Word or phrase, in language
+    Word,
+    /// Broad transcription
+    Ipa,
+    /// Narrow transcription
+    Narrow,
+    Gloss,
+    Note,
+    /// Forms of words after stage of sound changes, with name of stage
+    Stage(String),
+    /// Other column, with name as written, which can start with format character like a table
+    Other(String),
+}
+
+impl Column {
+    fn from_name(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "word" => Column::Word,
+            "ipa" => Column::Ipa,
+            "narrow" => Column::Narrow,
+            "gloss" => Column::Gloss,
+            "note" | "notes" => Column::Note,
+            _ => Column::Other(name.to_string()),
+        }
+    }
+
+    /// Name of column in header row of `.llst` file
+    fn name(&self) -> &str {
+        match self {
+            Column::Word => "word",
+            Column::Ipa => "ipa",
+            Column::Narrow => "narrow",
+            Column::Gloss => "gloss",
+            Column::Note => "note",
+            Column::Stage(name) | Column::Other(name) => name,
+        }
+    }
+
+    /// Header cell of `{|` table, with format character of column
+    fn header(&self) -> String {
+        match self {
+            Column::Word => "'Word".to_string(),
+            Column::Ipa => "/IPA".to_string(),
+            Column::Narrow => "[IPA".to_string(),
+            Column::Gloss => "Gloss".to_string(),
+            Column::Note => "Notes".to_string(),
+            Column::Stage(name) => format!("'{}", name),
+            Column::Other(name) => name.clone(),
+        }
+    }
+}
+
+impl WordList {
+    pub fn parse(file: &str, filepath: &str, diagnostics: &mut Diagnostics) -> Self {
+        let mut title = None;
+        let mut columns: Option<Vec<Column>> = None;
+        let mut rows = Vec::new();
+
+        for (line_num, line) in file.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let cells = line.split('|').map(str::trim);
+
+            match &columns {
+                None => match line.strip_prefix("# ") {
+                    Some(text) if title.is_none() => title = Some(text.trim().to_string()),
+                    _ => columns = Some(cells.map(Column::from_name).collect()),
+                },
+                Some(columns) => {
+                    let row = cells.map(String::from).collect::<Vec<_>>();
+                    let error = |message| Diagnostic::error(filepath, Some(line_num + 1), message);
+                    if row.len() > columns.len() {
+                        diagnostics.push(error(format!(
+                            "row has {} cells, but word list has {} columns",
+                            row.len(),
+                            columns.len()
+                        )));
+                    }
+                    // Table statement would end at brace
+                    if line.contains(['{', '}']) {
+                        diagnostics.push(error("word list cannot contain `{` or `}`".to_string()));
+                    }
+                    rows.push(row);
+                }
+            }
+        }
+
+        WordList {
+            title,
+            columns: columns.unwrap_or_default(),
+            rows,
+        }
+    }
+
+    /// Word list as `{|` table statement of `.ling` file
+    pub fn to_table(&self) -> String {
+        self.table(Column::header)
+    }
+
+    /// Word list as `{|` table statement, with `header` as header cell of `column`, such as
+    /// `'Proto` instead of `'Word`
+    pub fn to_table_with_header(&self, column: &Column, header: &str) -> String {
+        self.table(|other| {
+            if other == column {
+                header.to_string()
+            } else {
+                other.header()
+            }
+        })
+    }
+
+    fn table(&self, header: impl Fn(&Column) -> String) -> String {
+        let mut lines = vec![
+            "{|".to_string(),
+            self.columns
+                .iter()
+                .map(header)
+                .collect::<Vec<_>>()
+                // Format character must be first character of header cell
+                .join(" |"),
+        ];
+        for row in &self.rows {
+            lines.push(
+                row.iter()
+                    .take(self.columns.len())
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+            );
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    /// Word list as `.llst` file
+    pub fn to_llst(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(title) = &self.title {
+            lines.push(format!("# {}\n", title));
+        }
+        lines.push(
+            self.columns
+                .iter()
+                .map(Column::name)
+                .collect::<Vec<_>>()
+                .join(" | "),
+        );
+        for row in &self.rows {
+            lines.push(row.join(" | "));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Word list as `.ling` file, with title as header
+    pub fn to_ling(&self) -> String {
+        match &self.title {
+            Some(title) => format!("# {}\n\n{}\n", title, self.to_table()),
+            None => self.to_table() + "\n",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_works() {
+        let mut diagnostics = Diagnostics::default();
+        let list = WordList::parse(
+            "# Colours\n\nword | ipa | gloss | 'Esperanto\nruĝa | ˈru.dʒa | red {x}\nblua | | blue | blua | extra\n",
+            "colours.llst",
+            &mut diagnostics,
+        );
+
+        assert_eq!(list.title.as_deref(), Some("Colours"));
+        assert_eq!(
+            list.columns,
+            vec![
+                Column::Word,
+                Column::Ipa,
+                Column::Gloss,
+                Column::Other("'Esperanto".to_string())
+            ]
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "error: colours.llst:4: word list cannot contain `{` or `}`",
+                "error: colours.llst:5: row has 5 cells, but word list has 4 columns"
+            ]
+        );
+        assert_eq!(
+            list.to_table(),
+            "{|\n'Word |/IPA |Gloss |'Esperanto\nruĝa | ˈru.dʒa | red {x}\nblua |  | blue | blua\n}"
+        );
+    }
+}